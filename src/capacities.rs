@@ -1,65 +1,296 @@
-use super::categories::{Category, CATEGORIES};
-use super::levels;
-use super::modes::{Mode, MODES};
-use super::time_bins::{self, TimeBin};
-use super::trips::{Trip, TRIPS};
+//! Remaining capacities of the trips, the pegel levels and the modes while
+//! plans are extracted, with the slack their tolerances allow.
+
+use super::categories::Category;
+use super::dataset::Dataset;
+use super::modes::Mode;
+use super::time_bins::{self, TimeBin, TIME_BINS};
+use super::trips::Trip;
+use csv::StringRecord;
 use std::convert::TryInto;
 
 type Count = usize;
 
+/// Allowed overuse of a capacity: a share of its count plus a fixed number of trips.
+#[derive(Clone, Copy, Debug)]
+pub struct Tolerance {
+    /// Share of the count, rounded down.
+    pub relative: f64,
+    /// Trips on top of the share.
+    pub absolute: Count,
+}
+impl Tolerance {
+    /// No overuse at all.
+    pub const NONE: Tolerance = Tolerance {
+        relative: 0f64,
+        absolute: 0,
+    };
+    fn slack(&self, count: Count) -> Count {
+        (count as f64 * self.relative).floor() as Count + self.absolute
+    }
+}
+/// Tolerances of the level and the mode capacities; trip capacities have none.
+#[derive(Clone, Copy, Debug)]
+pub struct Tolerances {
+    /// Tolerance of each category and time bin.
+    pub levels: Tolerance,
+    /// Tolerance of each mode.
+    pub modes: Tolerance,
+}
+
+/// A level or mode capacity used `count` times beyond its count, within its slack.
+pub struct Overuse {
+    /// "level" or "mode".
+    pub capacity: &'static str,
+    /// Category id or mode name.
+    pub key: String,
+    /// Time bin of a level; modes have none.
+    pub time_bin: Option<TimeBin>,
+    /// Trips beyond the count.
+    pub count: Count,
+    /// Overuse the tolerance allowed.
+    pub slack: Count,
+}
+impl Overuse {
+    /// Column names of `to_record`.
+    pub const HEADER: [&'static str; 5] = ["capacity", "key", "time_bin", "overuse", "slack"];
+    /// Fields in the order of `HEADER`.
+    pub fn to_record(&self) -> Vec<String> {
+        vec![
+            self.capacity.to_string(),
+            self.key.clone(),
+            self.time_bin
+                .map_or(String::new(), |time_bin| time_bin.value().to_string()),
+            self.count.to_string(),
+            self.slack.to_string(),
+        ]
+    }
+}
+
+/// Remaining counts; those of levels and modes include their slack, so that
+/// a remaining count below the slack is the overuse of the capacity.
+#[derive(Clone)]
 pub struct Capacities {
     of_trips: Vec<Count>,
     of_levels: Vec<[Count; time_bins::COUNT]>,
     of_modes: Vec<Count>,
+    slack_of_levels: Vec<[Count; time_bins::COUNT]>,
+    slack_of_modes: Vec<Count>,
 }
 impl Capacities {
-    pub fn new() -> Capacities {
-        let of_trips = TRIPS.iter().map(|trip| trip.count).collect();
+    /// Full capacities of `dataset` with the slack of `tolerances`.
+    pub fn new(dataset: &Dataset, tolerances: &Tolerances) -> Capacities {
+        let trips = &dataset.trips;
+        let of_trips = trips.iter().map(|trip| trip.count).collect();
         let mut of_levels: Vec<[Count; time_bins::COUNT]> = Vec::new();
-        for category in CATEGORIES.iter() {
-            let total = TRIPS
+        for category in dataset.categories.iter() {
+            let total = trips
                 .iter()
                 .filter(|&trip| trip.category.eq(category))
                 .count() as f64;
-            let values = levels::get_levels(category)
+            let values = dataset
+                .levels_of(category)
                 .iter()
                 .map(|share| share * total)
                 .collect::<Vec<f64>>();
             of_levels.push(sum_safe_round(&values).try_into().unwrap());
         }
         let of_modes: Vec<Count> = sum_safe_round(
-            &MODES
+            &dataset
+                .modes
                 .iter()
-                .map(|mode| mode.share * (TRIPS.len() as f64))
+                .map(|mode| mode.share * (trips.len() as f64))
                 .collect::<Vec<f64>>(),
         );
+        let mut slack_of_levels = of_levels.clone();
+        for (slacks, levels) in slack_of_levels.iter_mut().zip(of_levels.iter_mut()) {
+            for (slack, level) in slacks.iter_mut().zip(levels.iter_mut()) {
+                *slack = tolerances.levels.slack(*level);
+                *level += *slack;
+            }
+        }
+        let slack_of_modes: Vec<Count> = of_modes
+            .iter()
+            .map(|&mode| tolerances.modes.slack(mode))
+            .collect();
+        let of_modes = of_modes
+            .iter()
+            .zip(slack_of_modes.iter())
+            .map(|(mode, slack)| mode + slack)
+            .collect();
         Capacities {
             of_trips,
             of_levels,
             of_modes,
+            slack_of_levels,
+            slack_of_modes,
+        }
+    }
+    pub(crate) fn to_records(&self) -> Vec<Vec<String>> {
+        let mut records: Vec<Vec<String>> = Vec::new();
+        let tagged = |tag: &str, values: &[Count]| {
+            let mut record = vec![tag.to_string()];
+            record.extend(values.iter().map(|value| value.to_string()));
+            record
+        };
+        records.push(tagged("trips", &self.of_trips));
+        for levels in self.of_levels.iter() {
+            records.push(tagged("levels", levels));
+        }
+        records.push(tagged("modes", &self.of_modes));
+        for slacks in self.slack_of_levels.iter() {
+            records.push(tagged("levels_slack", slacks));
+        }
+        records.push(tagged("modes_slack", &self.slack_of_modes));
+        records
+    }
+    /// Inverse of `to_records` for capacities of `dataset`; fails for
+    /// malformed records and those of another dataset.
+    pub(crate) fn from_records(
+        dataset: &Dataset,
+        records: &[StringRecord],
+    ) -> Result<Capacities, String> {
+        let values = |record: &StringRecord| -> Result<Vec<Count>, String> {
+            record
+                .iter()
+                .skip(1)
+                .map(|value| {
+                    value
+                        .parse()
+                        .map_err(|_| format!("Invalid {} capacity \"{}\"!", &record[0], value))
+                })
+                .collect()
+        };
+        let bins = |record: &StringRecord| -> Result<[Count; time_bins::COUNT], String> {
+            values(record)?.try_into().map_err(|_| {
+                format!(
+                    "Expected {} time bins of {} capacities!",
+                    time_bins::COUNT,
+                    &record[0]
+                )
+            })
+        };
+        let tagged = |tag: &'static str| records.iter().filter(move |record| &record[0] == tag);
+        let missing = |tag: &str| format!("Missing {} capacities!", tag);
+        let of_trips = values(tagged("trips").next().ok_or_else(|| missing("trip"))?)?;
+        let of_levels = tagged("levels")
+            .map(bins)
+            .collect::<Result<Vec<[Count; time_bins::COUNT]>, String>>()?;
+        let of_modes = values(tagged("modes").next().ok_or_else(|| missing("mode"))?)?;
+        let mut slack_of_levels = tagged("levels_slack")
+            .map(bins)
+            .collect::<Result<Vec<[Count; time_bins::COUNT]>, String>>()?;
+        slack_of_levels.resize(of_levels.len(), [0; time_bins::COUNT]);
+        let slack_of_modes = match tagged("modes_slack").next() {
+            Some(record) => values(record)?,
+            None => vec![0; of_modes.len()],
+        };
+        let expect = |name: &str, count: usize, expected: usize| {
+            if count == expected {
+                Ok(())
+            } else {
+                Err(format!(
+                    "Expected capacities of {} {}, found {}!",
+                    expected, name, count
+                ))
+            }
+        };
+        expect("trips", of_trips.len(), dataset.trips.len())?;
+        expect(
+            "level categories",
+            of_levels.len(),
+            dataset.categories.len(),
+        )?;
+        expect("modes", of_modes.len(), dataset.modes.len())?;
+        expect("mode slacks", slack_of_modes.len(), dataset.modes.len())?;
+        Ok(Capacities {
+            of_trips,
+            of_levels,
+            of_modes,
+            slack_of_levels,
+            slack_of_modes,
+        })
+    }
+    /// Every level and mode used beyond its count.
+    pub fn overuse(&self, dataset: &Dataset) -> Vec<Overuse> {
+        let mut overuse: Vec<Overuse> = Vec::new();
+        for category in dataset.categories.iter() {
+            for &time_bin in TIME_BINS.iter() {
+                let slack = self.slack_of_levels[category.index][time_bin.value()];
+                let count = slack.saturating_sub(self.get_level(category, time_bin));
+                if count > 0 {
+                    overuse.push(Overuse {
+                        capacity: "level",
+                        key: category.id.value().to_string(),
+                        time_bin: Some(time_bin),
+                        count,
+                        slack,
+                    });
+                }
+            }
         }
+        for mode in dataset.modes.iter() {
+            let slack = self.slack_of_modes[mode.index];
+            let count = slack.saturating_sub(self.get_mode(mode));
+            if count > 0 {
+                overuse.push(Overuse {
+                    capacity: "mode",
+                    key: mode.name.to_string(),
+                    time_bin: None,
+                    count,
+                    slack,
+                });
+            }
+        }
+        overuse
+    }
+    /// Remaining count of all trips.
+    pub fn trip_total(&self) -> Count {
+        self.of_trips.iter().sum()
     }
+    /// Remaining count of a trip.
     pub fn get_trip(&self, trip: &Trip) -> Count {
         self.of_trips[trip.index]
     }
+    /// Remaining level count of a category in a time bin, including the slack.
     pub fn get_level(&self, category: &Category, time_bin: TimeBin) -> Count {
         self.of_levels[category.index][time_bin.value()]
     }
+    /// Remaining mode count, including the slack.
     pub fn get_mode(&self, mode: &Mode) -> Count {
         self.of_modes[mode.index]
     }
-    pub fn reduce_trip(&mut self, trip: &Trip, count: Count) {
+    /// Remaining level count, without the slack.
+    pub fn unused_level(&self, category: &Category, time_bin: TimeBin) -> Count {
+        self.get_level(category, time_bin)
+            .saturating_sub(self.slack_of_levels[category.index][time_bin.value()])
+    }
+    /// Remaining mode count, without the slack.
+    pub fn unused_mode(&self, mode: &Mode) -> Count {
+        self.get_mode(mode)
+            .saturating_sub(self.slack_of_modes[mode.index])
+    }
+    pub(crate) fn reduce_trip(&mut self, trip: &Trip, count: Count) {
         assert!(count <= self.of_trips[trip.index]);
         self.of_trips[trip.index] -= count;
     }
-    pub fn reduce_level(&mut self, category: &Category, time_bin: TimeBin, count: Count) {
+    pub(crate) fn reduce_level(&mut self, category: &Category, time_bin: TimeBin, count: Count) {
         assert!(count <= self.of_levels[category.index][time_bin.value()]);
         self.of_levels[category.index][time_bin.value()] -= count;
     }
-    pub fn reduce_mode(&mut self, mode: &Mode, count: Count) {
+    pub(crate) fn reduce_mode(&mut self, mode: &Mode, count: Count) {
         assert!(count <= self.of_modes[mode.index]);
         self.of_modes[mode.index] -= count;
     }
+    pub(crate) fn release_trip(&mut self, trip: &Trip, count: Count) {
+        self.of_trips[trip.index] += count;
+    }
+    pub(crate) fn release_level(&mut self, category: &Category, time_bin: TimeBin, count: Count) {
+        self.of_levels[category.index][time_bin.value()] += count;
+    }
+    pub(crate) fn release_mode(&mut self, mode: &Mode, count: Count) {
+        self.of_modes[mode.index] += count;
+    }
 }
 
 fn sum_safe_round(values: &[f64]) -> Vec<usize> {
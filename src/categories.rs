@@ -1,22 +1,33 @@
+//! Trip categories of `categoryInformation.txt`: the purposes a trip leaves
+//! and reaches.
+
 use super::io;
 use super::purposes::Purpose;
-use lazy_static::lazy_static;
 use std::hash::{Hash, Hasher};
+use std::path::Path;
 use std::str::FromStr;
 
-#[derive(PartialEq, Eq)]
+/// Number of a category in the input files.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub struct Id(u8);
 impl Id {
+    /// The number itself.
     pub fn value(&self) -> u8 {
         self.0
     }
 }
+/// Trips from an activity of one purpose to one of another.
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
 pub struct Category {
+    /// Index in the categories of the dataset.
     pub index: usize,
+    /// Number in the input files.
     pub id: Id,
+    /// Purpose of the activity a trip leaves.
     pub origin: Purpose,
+    /// Purpose of the activity a trip reaches.
     pub destination: Purpose,
-    _priv: (),
 }
 impl PartialEq for Category {
     fn eq(&self, other: &Self) -> bool {
@@ -30,39 +41,40 @@ impl Hash for Category {
     }
 }
 
-lazy_static! {
-    pub static ref CATEGORIES: Vec<Category> = load();
-}
-
-fn load() -> Vec<Category> {
-    let records = io::read_csv(
-        "verkehrsfluss/verkehrsflussdaten/categoryInformation.txt",
-        false,
-        false,
-        b';',
-        None,
-    );
+/// Categories of `categoryInformation.txt`, in file order.
+pub(crate) fn load(directory: &Path) -> Result<Vec<Category>, String> {
+    let path = directory.join("verkehrsflussdaten/categoryInformation.txt");
+    let records = io::try_read_csv(&path, false, false, b';', None)?;
+    let in_file = |error: String| format!("{}: {}", path.display(), error);
     let mut categories: Vec<Category> = Vec::new();
     for record in records {
-        let split = record[2].split("->").collect::<Vec<&str>>();
-        let id: u8 = record[0].parse().unwrap();
+        let id: u8 = io::parse_field(&record, 0, "category id").map_err(in_file)?;
         if categories
             .iter()
             .find(|&category| category.id.0 == id)
             .is_some()
         {
-            panic!("Duplicate category id!");
+            return Err(in_file(format!("Duplicate category id {}!", id)));
         }
-        let origin = Purpose::from_str(split[0]).unwrap();
-        let destination = Purpose::from_str(split[1]).unwrap();
+        let purposes: String = io::parse_field(&record, 2, "purposes").map_err(in_file)?;
+        let (origin, destination) = match purposes.split_once("->") {
+            Some((origin, destination)) => (
+                Purpose::from_str(origin).map_err(in_file)?,
+                Purpose::from_str(destination).map_err(in_file)?,
+            ),
+            None => {
+                return Err(in_file(format!(
+                    "Invalid purposes \"{}\", expected \"origin->destination\"!",
+                    purposes
+                )))
+            }
+        };
         categories.push(Category {
             index: categories.len(),
             id: Id(id),
             origin,
             destination,
-            _priv: (),
         });
     }
-    println!("Loaded {} categories.", categories.len());
-    categories
+    Ok(categories)
 }
@@ -0,0 +1,141 @@
+//! Activity chain patterns of plans and their comparison with a reference.
+
+use super::io;
+use super::plans::{self, Plan};
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::Path;
+
+const HEADER: [&str; 4] = ["stage", "pattern", "plans", "share"];
+const DEVIATION_HEADER: [&str; 4] = ["pattern", "reference_share", "realized_share", "deviation"];
+const PRINTED_PATTERNS: usize = 5;
+
+/// Patterns with their number of plans.
+type Frequencies = Vec<(String, usize)>;
+
+/// Purpose codes of the activities of a plan, e.g. "H-W-S-H".
+pub fn pattern(plan: &Plan) -> String {
+    let (last_node, last_edge) = plan.legs.last().unwrap();
+    plan.legs
+        .iter()
+        .map(|(node, _)| node.purpose)
+        .chain(std::iter::once(last_edge.target(last_node).purpose))
+        .map(|purpose| purpose.code())
+        .join("-")
+}
+
+/// Patterns by descending frequency, ties by pattern.
+pub fn frequencies<'a, 'd: 'a, I: Iterator<Item = &'a Plan<'d>>>(plans: I) -> Frequencies {
+    let mut counts: Frequencies = plans.map(pattern).counts().into_iter().collect();
+    counts.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+    counts
+}
+
+/// Writes the frequency of each activity chain pattern per stage and over all
+/// plans. Returns the most frequent patterns over all plans as a summary line.
+pub fn write<S: AsRef<OsStr>>(path: S, plans: &[Plan]) -> String {
+    let mut records: Vec<Vec<String>> = vec![HEADER.iter().map(|s| s.to_string()).collect()];
+    // plans of the improvement last, after those of the stages
+    let stages: Vec<usize> = plans
+        .iter()
+        .map(|plan| plan.stage)
+        .unique()
+        .sorted_by_key(|&stage| (stage == plans::IMPROVED, stage))
+        .collect();
+    let mut groups: Vec<(String, Frequencies, usize)> = stages
+        .into_iter()
+        .map(|stage| {
+            let stage_plans = plans.iter().filter(|plan| plan.stage == stage);
+            let count = stage_plans.clone().count();
+            (plans::stage_name(stage), frequencies(stage_plans), count)
+        })
+        .collect();
+    groups.push(("all".to_string(), frequencies(plans.iter()), plans.len()));
+    for (stage, counts, total) in groups.iter() {
+        for (pattern, count) in counts.iter() {
+            records.push(vec![
+                stage.to_string(),
+                pattern.to_string(),
+                count.to_string(),
+                format!("{:.4}", *count as f64 / *total as f64),
+            ]);
+        }
+    }
+    io::write_csv(path, b';', &records);
+
+    let all = &groups.last().unwrap().1;
+    format!(
+        "Chain patterns: {}distinct: {}",
+        all.iter()
+            .take(PRINTED_PATTERNS)
+            .map(|(pattern, count)| format!("{}: {} | ", pattern, count))
+            .join(""),
+        all.len()
+    )
+}
+
+/// Shares of activity chain patterns to compare the plans with.
+pub struct Reference(HashMap<String, f64>);
+
+/// Reads a reference file of "pattern;value" records with header, whose
+/// values are normalized to shares.
+pub fn read_reference<S: AsRef<OsStr>>(path: S) -> Result<Reference, String> {
+    let path = Path::new(&path);
+    let records = io::try_read_csv(path, false, true, b';', None)?;
+    let in_file = |error: String| format!("{}: {}", path.display(), error);
+    let mut values: HashMap<String, f64> = HashMap::new();
+    for record in records.iter() {
+        let pattern: String = io::parse_field(record, 0, "pattern").map_err(in_file)?;
+        let value: f64 = io::parse_field(record, 1, "reference value").map_err(in_file)?;
+        if !value.is_finite() || value < 0f64 {
+            return Err(in_file(format!(
+                "Reference value {} of \"{}\" is not a non-negative number!",
+                value, pattern
+            )));
+        }
+        *values.entry(pattern).or_default() += value;
+    }
+    let total: f64 = values.values().sum();
+    if total <= 0f64 {
+        return Err(in_file("The reference values sum to zero!".to_string()));
+    }
+    Ok(Reference(
+        values
+            .into_iter()
+            .map(|(pattern, value)| (pattern, value / total))
+            .collect(),
+    ))
+}
+
+/// Compares the pattern shares over all plans with `reference`. Returns the
+/// total variation distance as a summary line.
+pub fn compare<S: AsRef<OsStr>>(
+    reference: &Reference,
+    deviation_path: S,
+    plans: &[Plan],
+) -> String {
+    let realized: HashMap<String, usize> = frequencies(plans.iter()).into_iter().collect();
+
+    let mut records: Vec<Vec<String>> =
+        vec![DEVIATION_HEADER.iter().map(|s| s.to_string()).collect()];
+    let mut total_variation = 0f64;
+    for pattern in reference.0.keys().chain(realized.keys()).unique().sorted() {
+        let reference_share = reference.0.get(pattern).copied().unwrap_or(0f64);
+        let realized_share = realized
+            .get(pattern)
+            .map_or(0f64, |&count| count as f64 / plans.len() as f64);
+        total_variation += (realized_share - reference_share).abs() / 2f64;
+        records.push(vec![
+            pattern.to_string(),
+            format!("{:.4}", reference_share),
+            format!("{:.4}", realized_share),
+            format!("{:.4}", realized_share - reference_share),
+        ]);
+    }
+    io::write_csv(deviation_path, b';', &records);
+    format!(
+        "Chain patterns deviate from the reference by a total variation of {:.3}.",
+        total_variation
+    )
+}
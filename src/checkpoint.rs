@@ -0,0 +1,207 @@
+use super::capacities::Capacities;
+use super::constraints::Rejections;
+use super::dataset::Dataset;
+use super::io;
+use super::plans::{self, Plan};
+use super::search::Config;
+use super::stopping::StageProgress;
+use csv::StringRecord;
+use std::fs;
+use std::time::Duration;
+
+const FILE_NAME: &str = "checkpoint.csv";
+
+/// Last chunk that was completely processed.
+#[derive(Clone, Copy)]
+pub struct Position {
+    pub stage: usize,
+    pub chunk: usize,
+    pub total_steps: u64,
+    pub progress: StageProgress,
+}
+
+pub struct Checkpoint<'a> {
+    pub position: Position,
+    pub capacities: Capacities,
+    pub plans: Vec<Plan<'a>>,
+    pub rejections: Vec<Rejections>,
+}
+
+pub fn write(
+    config: &Config,
+    dataset: &Dataset,
+    position: &Position,
+    capacities: &Capacities,
+    plans: &[Plan],
+    rejections: &[Rejections],
+) {
+    let mut records = vec![
+        vec!["format".to_string(), plans::FORMAT_VERSION.to_string()],
+        vec![
+            "scaling".to_string(),
+            dataset.scaling.factor.to_string(),
+            dataset.scaling.integerization.to_string(),
+        ],
+        vec![
+            "dataset".to_string(),
+            dataset.trips.len().to_string(),
+            dataset.trip_total().to_string(),
+        ],
+        vec![
+            "search".to_string(),
+            config.chunks.to_string(),
+            config.stages.len().to_string(),
+        ],
+        vec![
+            "position".to_string(),
+            position.stage.to_string(),
+            position.chunk.to_string(),
+            position.total_steps.to_string(),
+            position.progress.elapsed.as_millis().to_string(),
+            position.progress.steps.to_string(),
+            position.progress.idle_chunks.to_string(),
+        ],
+    ];
+    records.append(&mut capacities.to_records());
+    for mut record in plans::to_records(dataset, plans) {
+        record.insert(0, "leg".to_string());
+        records.push(record);
+    }
+    for (stage, stage_rejections) in rejections.iter().enumerate() {
+        for mut record in stage_rejections.to_records(stage) {
+            record.insert(0, "rejections".to_string());
+            records.push(record);
+        }
+    }
+    // write next to the old checkpoint first, so a kill never leaves a partial one
+    let path = config.output.join(FILE_NAME);
+    let temp_path = path.with_extension("csv.tmp");
+    io::write_csv(&temp_path, b';', &records);
+    fs::rename(&temp_path, path).unwrap();
+}
+
+/// The last checkpoint in the output directory of `config`, if any. Fails
+/// for malformed checkpoints and those of another format version, written
+/// for another dataset or scaling of the trips, or with another number of
+/// chunks or stages.
+pub fn read<'a>(config: &Config, dataset: &'a Dataset) -> Result<Option<Checkpoint<'a>>, String> {
+    let path = config.output.join(FILE_NAME);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let records = io::try_read_csv(&path, false, false, b';', None)?;
+    let tagged_record = |tag: &str| records.iter().find(|record| &record[0] == tag);
+    let format = tagged_record("format").map_or("1", |record| &record[1]);
+    if format != plans::FORMAT_VERSION.to_string() {
+        return Err(format!(
+            "Checkpoint \"{}\" has format version {}, expected {}; delete it to start over!",
+            path.display(),
+            format,
+            plans::FORMAT_VERSION
+        ));
+    }
+    let expect = |tag: &str, values: Vec<String>, description: String| {
+        let found = tagged_record(tag).map(|record| record.iter().skip(1).collect::<Vec<&str>>());
+        if found == Some(values.iter().map(String::as_str).collect()) {
+            Ok(())
+        } else {
+            Err(format!(
+                "Checkpoint \"{}\" was written {}; delete it to start over!",
+                path.display(),
+                description
+            ))
+        }
+    };
+    let scaling = &dataset.scaling;
+    expect(
+        "scaling",
+        vec![
+            scaling.factor.to_string(),
+            scaling.integerization.to_string(),
+        ],
+        format!(
+            "with another trip scaling than factor {} and {} integerization",
+            scaling.factor, scaling.integerization
+        ),
+    )?;
+    expect(
+        "dataset",
+        vec![
+            dataset.trips.len().to_string(),
+            dataset.trip_total().to_string(),
+        ],
+        format!(
+            "for another dataset than {} trips of {} in total",
+            dataset.trips.len(),
+            dataset.trip_total()
+        ),
+    )?;
+    expect(
+        "search",
+        vec![config.chunks.to_string(), config.stages.len().to_string()],
+        format!(
+            "with another search than {} chunks and {} stages",
+            config.chunks,
+            config.stages.len()
+        ),
+    )?;
+    let invalid = |error: String| format!("Invalid checkpoint \"{}\": {}", path.display(), error);
+    let position = tagged_record("position")
+        .ok_or_else(|| "Missing position!".to_string())
+        .and_then(parse_position)
+        .map_err(invalid)?;
+    if position.stage >= config.stages.len() || position.chunk >= config.chunks {
+        return Err(invalid(format!(
+            "Position after stage {}, chunk {} is out of range!",
+            position.stage + 1,
+            position.chunk + 1
+        )));
+    }
+    let capacities = Capacities::from_records(dataset, &records).map_err(invalid)?;
+    let tagged = |tag: &str| -> Vec<StringRecord> {
+        records
+            .iter()
+            .filter(|record| &record[0] == tag)
+            .map(|record| record.iter().skip(1).collect())
+            .collect()
+    };
+    let leg_records = tagged("leg");
+    let rejection_records = tagged("rejections");
+    Ok(Some(Checkpoint {
+        position,
+        capacities,
+        plans: plans::from_records(dataset, &leg_records).map_err(invalid)?,
+        rejections: config.stages[..=position.stage]
+            .iter()
+            .enumerate()
+            .map(|(index, stage)| {
+                Rejections::from_records(&rejection_records, index, stage.filter_params.constraints)
+            })
+            .collect::<Result<Vec<Rejections>, String>>()
+            .map_err(invalid)?,
+    }))
+}
+
+fn parse_position(record: &StringRecord) -> Result<Position, String> {
+    if record.len() != 7 {
+        return Err(format!(
+            "Expected 7 position fields, found {}!",
+            record.len()
+        ));
+    }
+    let number = |index: usize| -> Result<u64, String> {
+        record[index]
+            .parse()
+            .map_err(|_| format!("Invalid position \"{}\"!", &record[index]))
+    };
+    Ok(Position {
+        stage: number(1)? as usize,
+        chunk: number(2)? as usize,
+        total_steps: number(3)?,
+        progress: StageProgress {
+            elapsed: Duration::from_millis(number(4)?),
+            steps: number(5)?,
+            idle_chunks: number(6)? as usize,
+        },
+    })
+}
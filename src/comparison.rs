@@ -0,0 +1,334 @@
+//! Differences between two sets of plans and their significance.
+
+use super::chains;
+use super::dataset::Dataset;
+use super::graph::{Edge, Node};
+use super::io;
+use super::plans::{self, Plan};
+use super::time_bins;
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+
+const HEADER: [&str; 8] = [
+    "kind",
+    "key",
+    "run_a",
+    "run_b",
+    "difference",
+    "z",
+    "p",
+    "significant",
+];
+/// False discovery rate of the differences marked as significant.
+const FALSE_DISCOVERY_RATE: f64 = 0.05;
+const PRINTED_SHIFTS: usize = 10;
+
+/// z of the difference between two counts, taken as Poisson distributed.
+fn count_z(a: usize, b: usize) -> f64 {
+    if a + b == 0 {
+        0f64
+    } else {
+        (b as f64 - a as f64) / ((a + b) as f64).sqrt()
+    }
+}
+
+/// z of the difference between the shares `a / a_total` and `b / b_total`,
+/// with the pooled share for the standard error.
+fn share_z(a: usize, a_total: usize, b: usize, b_total: usize) -> f64 {
+    if a_total == 0 || b_total == 0 {
+        return 0f64;
+    }
+    let pooled = (a + b) as f64 / (a_total + b_total) as f64;
+    let inverse_totals = 1f64 / a_total as f64 + 1f64 / b_total as f64;
+    let error = (pooled * (1f64 - pooled) * inverse_totals).sqrt();
+    if error == 0f64 {
+        0f64
+    } else {
+        (b as f64 / b_total as f64 - a as f64 / a_total as f64) / error
+    }
+}
+
+/// Share of the legs matching `predicate` among all legs of `plans`, and its
+/// variance as a ratio estimator over plans, since the legs of a plan are not
+/// independent of each other.
+fn leg_share(plans: &[Plan], predicate: impl Fn(&Node, &Edge) -> bool) -> (f64, f64) {
+    let per_plan: Vec<(f64, f64)> = plans
+        .iter()
+        .map(|plan| {
+            let matching = plan
+                .legs
+                .iter()
+                .filter(|(node, edge)| predicate(node, edge))
+                .count();
+            (matching as f64, plan.legs.len() as f64)
+        })
+        .collect();
+    let legs: f64 = per_plan.iter().map(|(_, legs)| legs).sum();
+    if legs == 0f64 {
+        return (0f64, 0f64);
+    }
+    let ratio = per_plan.iter().map(|(matching, _)| matching).sum::<f64>() / legs;
+    let n = per_plan.len() as f64;
+    if n < 2f64 {
+        return (ratio, 0f64);
+    }
+    let residuals: f64 = per_plan
+        .iter()
+        .map(|(matching, legs)| (matching - ratio * legs).powi(2))
+        .sum();
+    (ratio, n / (n - 1f64) * residuals / legs.powi(2))
+}
+
+/// Two-sided p-value of a standard normal `z`.
+fn p_value(z: f64) -> f64 {
+    // complementary error function after Abramowitz and Stegun 7.1.26
+    let x = z.abs() / 2f64.sqrt();
+    let t = 1f64 / (1f64 + 0.327_591_1 * x);
+    let polynomial = t
+        * (0.254_829_592
+            + t * (-0.284_496_736
+                + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    (polynomial * (-x * x).exp()).min(1f64)
+}
+
+/// Marks the shifts significant by the Benjamini-Hochberg procedure.
+fn mark_significant(shifts: &mut [Shift]) {
+    let mut order: Vec<usize> = (0..shifts.len()).collect();
+    order.sort_by(|&x, &y| shifts[x].p.partial_cmp(&shifts[y].p).unwrap());
+    let tests = shifts.len() as f64;
+    let rejected = order
+        .iter()
+        .enumerate()
+        .filter(|(rank, &index)| {
+            shifts[index].p <= (*rank + 1) as f64 / tests * FALSE_DISCOVERY_RATE
+        })
+        .map(|(rank, _)| rank + 1)
+        .next_back()
+        .unwrap_or(0);
+    for &index in order[..rejected].iter() {
+        shifts[index].is_significant = true;
+    }
+}
+
+fn share(count: usize, total: usize) -> f64 {
+    if total == 0 {
+        0f64
+    } else {
+        count as f64 / total as f64
+    }
+}
+
+struct Shift {
+    kind: &'static str,
+    key: String,
+    a: String,
+    b: String,
+    difference: f64,
+    z: f64,
+    p: f64,
+    is_significant: bool,
+}
+impl Shift {
+    fn new(
+        kind: &'static str,
+        key: String,
+        (a, b): (String, String),
+        difference: f64,
+        z: f64,
+    ) -> Self {
+        Shift {
+            kind,
+            key,
+            a,
+            b,
+            difference,
+            z,
+            p: p_value(z),
+            is_significant: false,
+        }
+    }
+    fn count(kind: &'static str, key: String, a: usize, b: usize) -> Self {
+        Shift::new(
+            kind,
+            key,
+            (a.to_string(), b.to_string()),
+            b as f64 - a as f64,
+            count_z(a, b),
+        )
+    }
+    fn share(
+        kind: &'static str,
+        key: String,
+        (a, a_total): (usize, usize),
+        (b, b_total): (usize, usize),
+    ) -> Self {
+        let (a_share, b_share) = (share(a, a_total), share(b, b_total));
+        Shift::new(
+            kind,
+            key,
+            (format!("{:.4}", a_share), format!("{:.4}", b_share)),
+            b_share - a_share,
+            share_z(a, a_total, b, b_total),
+        )
+    }
+    /// Difference of leg shares from `leg_share`.
+    fn leg_share(
+        kind: &'static str,
+        key: String,
+        (a_share, a_variance): (f64, f64),
+        (b_share, b_variance): (f64, f64),
+    ) -> Self {
+        let error = (a_variance + b_variance).sqrt();
+        let z = if error == 0f64 {
+            0f64
+        } else {
+            (b_share - a_share) / error
+        };
+        Shift::new(
+            kind,
+            key,
+            (format!("{:.4}", a_share), format!("{:.4}", b_share)),
+            b_share - a_share,
+            z,
+        )
+    }
+    fn to_record(&self) -> Vec<String> {
+        vec![
+            self.kind.to_string(),
+            self.key.clone(),
+            self.a.clone(),
+            self.b.clone(),
+            format!("{:.4}", self.difference),
+            format!("{:.2}", self.z),
+            format!("{:.4}", self.p),
+            if self.is_significant { "*" } else { "" }.to_string(),
+        ]
+    }
+}
+
+/// Counts of what each plan set has, indexed alike for both.
+struct Counts {
+    plans: usize,
+    legs: usize,
+    by_stage: HashMap<usize, usize>,
+    patterns: HashMap<String, usize>,
+    by_category: Vec<usize>,
+}
+impl Counts {
+    fn new(dataset: &Dataset, plans: &[Plan]) -> Self {
+        let mut counts = Counts {
+            plans: plans.len(),
+            legs: 0,
+            by_stage: plans.iter().map(|plan| plan.stage).counts(),
+            patterns: chains::frequencies(plans.iter()).into_iter().collect(),
+            by_category: vec![0; dataset.categories.len()],
+        };
+        for (_, edge) in plans.iter().flat_map(|plan| plan.legs.iter()) {
+            counts.legs += 1;
+            counts.by_category[edge.trip.category.index] += 1;
+        }
+        counts
+    }
+}
+
+/// Compares two sets of plans: plan and leg counts, plans per stage, chain
+/// pattern, mode and departure time shares, and the trips used per category
+/// against the input. Mode and departure time shares are of legs, with standard
+/// errors over plans. Differences are marked as significant at a false
+/// discovery rate of 5% over all of them. Returns the number of significant
+/// differences and the largest ones as summary lines.
+pub fn write<S: AsRef<OsStr>>(
+    path: S,
+    dataset: &Dataset,
+    a_plans: &[Plan],
+    b_plans: &[Plan],
+) -> String {
+    let (a, b) = (Counts::new(dataset, a_plans), Counts::new(dataset, b_plans));
+
+    let mut shifts = vec![
+        Shift::count("plans", "count".to_string(), a.plans, b.plans),
+        Shift::count("legs", "count".to_string(), a.legs, b.legs),
+    ];
+    let stages = a
+        .by_stage
+        .keys()
+        .chain(b.by_stage.keys())
+        .unique()
+        .sorted_by_key(|&&stage| (stage == plans::IMPROVED, stage));
+    for stage in stages {
+        let count = |counts: &Counts| counts.by_stage.get(stage).copied().unwrap_or(0);
+        shifts.push(Shift::count(
+            "plans_by_stage",
+            plans::stage_name(*stage),
+            count(&a),
+            count(&b),
+        ));
+    }
+    for pattern in a.patterns.keys().chain(b.patterns.keys()).unique().sorted() {
+        let count = |counts: &Counts| counts.patterns.get(pattern).copied().unwrap_or(0);
+        shifts.push(Shift::share(
+            "pattern",
+            pattern.to_string(),
+            (count(&a), a.plans),
+            (count(&b), b.plans),
+        ));
+    }
+    for mode in dataset.modes.iter() {
+        let share = |plans: &[Plan]| leg_share(plans, |_, edge| edge.mode == mode);
+        shifts.push(Shift::leg_share(
+            "mode",
+            mode.name.to_string(),
+            share(a_plans),
+            share(b_plans),
+        ));
+    }
+    for bin in 0..time_bins::COUNT {
+        let departs = |node: &Node, _: &Edge| node.time_bin.value() == bin;
+        let (a_share, b_share) = (leg_share(a_plans, departs), leg_share(b_plans, departs));
+        if a_share.0 + b_share.0 > 0f64 {
+            shifts.push(Shift::leg_share(
+                "departure_time_bin",
+                bin.to_string(),
+                a_share,
+                b_share,
+            ));
+        }
+    }
+    for category in dataset.categories.iter() {
+        let total: usize = dataset
+            .trips
+            .iter()
+            .filter(|trip| trip.category.eq(category))
+            .map(|trip| trip.count)
+            .sum();
+        shifts.push(Shift::share(
+            "od_coverage",
+            category.id.value().to_string(),
+            (a.by_category[category.index], total),
+            (b.by_category[category.index], total),
+        ));
+    }
+
+    mark_significant(&mut shifts);
+    let mut records: Vec<Vec<String>> = vec![HEADER.iter().map(|s| s.to_string()).collect()];
+    records.extend(shifts.iter().map(Shift::to_record));
+    io::write_csv(path, b';', &records);
+
+    let mut significant: Vec<&Shift> = shifts.iter().filter(|shift| shift.is_significant).collect();
+    significant.sort_by(|x, y| y.z.abs().partial_cmp(&x.z.abs()).unwrap());
+    let mut summary = format!(
+        "Compared {} with {} plans: {} of {} differences significant.",
+        a.plans,
+        b.plans,
+        significant.len(),
+        shifts.len()
+    );
+    for shift in significant.iter().take(PRINTED_SHIFTS) {
+        summary += &format!(
+            "\n{} {}: {} -> {} (z = {:.2})",
+            shift.kind, shift.key, shift.a, shift.b, shift.z
+        );
+    }
+    summary
+}
@@ -0,0 +1,451 @@
+//! Constraints deciding which paths of the search are valid plans, and the
+//! number of branches each of them pruned.
+
+use super::capacities::Capacities;
+use super::filters::FilterParams;
+use super::graph::{Edge, Node};
+use super::modes;
+use super::reachability::Reachability;
+use super::time_bins;
+use csv::StringRecord;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Outcome of checking a path after a node and edge were appended.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Verdict {
+    /// Neither this path nor any extension of it can become valid.
+    Prune,
+    /// The path is not valid yet, but extensions of it might be.
+    Incomplete,
+    /// The path is a valid plan as far as this constraint is concerned.
+    Valid,
+}
+
+/// The current path, starting with the start node; `nodes` has one element more than `edges`.
+pub struct Path<'a> {
+    /// Activities of the path.
+    pub nodes: &'a [Node],
+    /// Trips between consecutive activities.
+    pub edges: &'a [Edge<'a>],
+}
+
+/// A condition on the paths of the search, built anew for each start node
+/// so that it may keep state along the current path.
+pub trait Constraint {
+    /// Name in the rejection statistics, unique among the constraints of a stage.
+    fn name(&self) -> &'static str;
+    /// Called after a node and edge were appended to the path, before checking.
+    fn push(&mut self, _path: &Path) {}
+    /// Called before the last node and edge are removed from the path.
+    fn pop(&mut self, _path: &Path) {}
+    /// Verdict on the current path.
+    fn check(&self, path: &Path) -> Verdict;
+}
+
+/// Everything a constraint may depend on when it is built for a search from one start node.
+#[derive(Clone)]
+pub struct Context {
+    /// Parameters of the stage.
+    pub params: FilterParams,
+    /// Remaining capacities when the search from the start node began.
+    pub capacities: Arc<Capacities>,
+    /// Bounds on the way back to each purpose.
+    pub reachability: Arc<Reachability>,
+}
+
+/// Builds a constraint for the search from one start node; `name` is the
+/// one its `Constraint::name` returns.
+#[derive(Clone, Copy)]
+pub struct Constructor {
+    /// Name of the constraint it builds.
+    pub name: &'static str,
+    /// Builds the constraint.
+    pub build: fn(&Context) -> Box<dyn Constraint>,
+}
+impl PartialEq for Constructor {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+impl fmt::Debug for Constructor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// Plans have a number of activities within the length range.
+pub const LENGTH: Constructor = Constructor {
+    name: "length",
+    build: Length::build,
+};
+/// Plans last at least the minimal duration and at most a day.
+pub const DURATION: Constructor = Constructor {
+    name: "duration",
+    build: DurationRange::build,
+};
+/// Plans end with the purpose they started with, if the stage asks for cycles.
+pub const CYCLE: Constructor = Constructor {
+    name: "cycle",
+    build: Cycle::build,
+};
+/// Prunes paths that cannot return to their first purpose in time, if the stage asks for cycles.
+pub const REACH: Constructor = Constructor {
+    name: "reach",
+    build: Reach::build,
+};
+/// Plans use no trip more often than its remaining count.
+pub const TRIP_CAPACITY: Constructor = Constructor {
+    name: "tripcap",
+    build: TripCapacity::build,
+};
+/// Plans start no more trips of a category in a time bin than its remaining level.
+pub const LEVEL_CAPACITY: Constructor = Constructor {
+    name: "levelcap",
+    build: LevelCapacity::build,
+};
+/// Plans use no mode more often than its remaining capacity.
+pub const MODE_CAPACITY: Constructor = Constructor {
+    name: "modecap",
+    build: ModeCapacity::build,
+};
+
+/// All built-in constraints, in the order they are cheapest to check.
+pub const DEFAULT: &[Constructor] = &[
+    LENGTH,
+    DURATION,
+    CYCLE,
+    REACH,
+    TRIP_CAPACITY,
+    LEVEL_CAPACITY,
+    MODE_CAPACITY,
+];
+
+/// Constraints on the remaining capacities; all others only depend on the path itself.
+pub const CAPACITY: &[Constructor] = &[TRIP_CAPACITY, LEVEL_CAPACITY, MODE_CAPACITY];
+
+/// Constraints of `constructors` for the search from the start node of `context`.
+pub(crate) fn build(constructors: &[Constructor], context: &Context) -> Vec<Box<dyn Constraint>> {
+    constructors
+        .iter()
+        .map(|constructor| {
+            let constraint = (constructor.build)(context);
+            debug_assert_eq!(constraint.name(), constructor.name);
+            constraint
+        })
+        .collect()
+}
+
+/// Number of branches each constraint pruned, by path depth in legs.
+#[derive(Clone, Default)]
+pub struct Rejections(HashMap<&'static str, Vec<u64>>);
+impl Rejections {
+    /// Counts a branch pruned by constraint `name` at `depth`.
+    pub(crate) fn record(&mut self, name: &'static str, depth: usize) {
+        self.add(name, depth, 1);
+    }
+    fn add(&mut self, name: &'static str, depth: usize, count: u64) {
+        let counts = self.0.entry(name).or_default();
+        if counts.len() <= depth {
+            counts.resize(depth + 1, 0);
+        }
+        counts[depth] += count;
+    }
+    /// Sum of both counts.
+    pub(crate) fn merge(mut self, other: Rejections) -> Rejections {
+        for (name, counts) in other.0 {
+            for (depth, count) in counts.into_iter().enumerate() {
+                self.add(name, depth, count);
+            }
+        }
+        self
+    }
+    /// Branches pruned by constraint `name` at any depth.
+    pub fn total(&self, name: &str) -> u64 {
+        self.0.get(name).map_or(0, |counts| counts.iter().sum())
+    }
+    /// Records of (stage, constraint, depth, count), sorted by constraint and depth.
+    pub fn to_records(&self, stage: usize) -> Vec<Vec<String>> {
+        let mut names: Vec<&&str> = self.0.keys().collect();
+        names.sort();
+        let mut records: Vec<Vec<String>> = Vec::new();
+        for name in names {
+            for (depth, count) in self.0[*name].iter().enumerate() {
+                if *count > 0 {
+                    records.push(vec![
+                        stage.to_string(),
+                        name.to_string(),
+                        depth.to_string(),
+                        count.to_string(),
+                    ]);
+                }
+            }
+        }
+        records
+    }
+    /// Inverse of `to_records` for the records of one stage, which checks
+    /// `constraints`.
+    pub fn from_records(
+        records: &[StringRecord],
+        stage: usize,
+        constraints: &[Constructor],
+    ) -> Result<Rejections, String> {
+        let number = |value: &str| -> Result<u64, String> {
+            value
+                .parse()
+                .map_err(|_| format!("Invalid rejection count \"{}\"!", value))
+        };
+        let mut rejections = Rejections::default();
+        for record in records.iter() {
+            if record.len() != 4 {
+                return Err(format!(
+                    "Expected 4 rejection fields, found {}!",
+                    record.len()
+                ));
+            }
+            if number(&record[0])? != stage as u64 {
+                continue;
+            }
+            let name = match constraints
+                .iter()
+                .find(|constraint| constraint.name == &record[1])
+            {
+                Some(constraint) => constraint.name,
+                None => {
+                    return Err(format!(
+                        "Constraint \"{}\" is not used by stage {}!",
+                        &record[1],
+                        stage + 1
+                    ))
+                }
+            };
+            rejections.add(name, number(&record[2])? as usize, number(&record[3])?);
+        }
+        Ok(rejections)
+    }
+}
+
+fn bool_verdict(ok: bool) -> Verdict {
+    if ok {
+        Verdict::Valid
+    } else {
+        Verdict::Prune
+    }
+}
+
+fn last_duration(path: &Path) -> usize {
+    let n = path.nodes.len();
+    (path.nodes[n - 1].time_bin - path.nodes[n - 2].time_bin) as usize
+}
+
+struct Length {
+    range: (usize, usize),
+}
+impl Length {
+    fn build(context: &Context) -> Box<dyn Constraint> {
+        Box::new(Length {
+            range: context.params.length_range,
+        })
+    }
+}
+impl Constraint for Length {
+    fn name(&self) -> &'static str {
+        "length"
+    }
+    fn check(&self, path: &Path) -> Verdict {
+        if path.nodes.len() < self.range.0 {
+            Verdict::Incomplete
+        } else {
+            bool_verdict(path.nodes.len() < self.range.1)
+        }
+    }
+}
+
+struct DurationRange {
+    min: usize,
+    duration: usize,
+}
+impl DurationRange {
+    fn build(context: &Context) -> Box<dyn Constraint> {
+        Box::new(DurationRange {
+            min: context.params.duration_min as usize,
+            duration: 0,
+        })
+    }
+}
+impl Constraint for DurationRange {
+    fn name(&self) -> &'static str {
+        "duration"
+    }
+    fn push(&mut self, path: &Path) {
+        self.duration += last_duration(path);
+    }
+    fn pop(&mut self, path: &Path) {
+        self.duration -= last_duration(path);
+    }
+    fn check(&self, _path: &Path) -> Verdict {
+        if self.duration > time_bins::COUNT {
+            Verdict::Prune
+        } else if self.duration < self.min {
+            Verdict::Incomplete
+        } else {
+            Verdict::Valid
+        }
+    }
+}
+
+struct Cycle {
+    enabled: bool,
+}
+impl Cycle {
+    fn build(context: &Context) -> Box<dyn Constraint> {
+        Box::new(Cycle {
+            enabled: context.params.cycle,
+        })
+    }
+}
+impl Constraint for Cycle {
+    fn name(&self) -> &'static str {
+        "cycle"
+    }
+    fn check(&self, path: &Path) -> Verdict {
+        let first = path.nodes.first().unwrap();
+        let last = path.nodes.last().unwrap();
+        if !self.enabled || last.purpose == first.purpose {
+            Verdict::Valid
+        } else {
+            Verdict::Incomplete
+        }
+    }
+}
+
+/// Prunes cyclic paths that cannot return to the first purpose within the
+/// length range and the time bins of a day.
+struct Reach {
+    enabled: bool,
+    max_length: usize,
+    reachability: Arc<Reachability>,
+    duration: usize,
+}
+impl Reach {
+    fn build(context: &Context) -> Box<dyn Constraint> {
+        Box::new(Reach {
+            enabled: context.params.cycle,
+            max_length: context.params.length_range.1,
+            reachability: context.reachability.clone(),
+            duration: 0,
+        })
+    }
+}
+impl Constraint for Reach {
+    fn name(&self) -> &'static str {
+        "reach"
+    }
+    fn push(&mut self, path: &Path) {
+        self.duration += last_duration(path);
+    }
+    fn pop(&mut self, path: &Path) {
+        self.duration -= last_duration(path);
+    }
+    fn check(&self, path: &Path) -> Verdict {
+        if !self.enabled {
+            return Verdict::Valid;
+        }
+        let purpose = path.nodes.first().unwrap().purpose;
+        let target = path.nodes.last().unwrap();
+        let legs = self.reachability.legs(target, purpose);
+        let bins = self.reachability.bins(target, purpose);
+        bool_verdict(
+            legs.saturating_add(path.nodes.len()) < self.max_length
+                && bins.saturating_add(self.duration) <= time_bins::COUNT,
+        )
+    }
+}
+
+struct TripCapacity {
+    capacities: Arc<Capacities>,
+}
+impl TripCapacity {
+    fn build(context: &Context) -> Box<dyn Constraint> {
+        Box::new(TripCapacity {
+            capacities: context.capacities.clone(),
+        })
+    }
+}
+impl Constraint for TripCapacity {
+    fn name(&self) -> &'static str {
+        "tripcap"
+    }
+    fn check(&self, path: &Path) -> Verdict {
+        let (edge, prev_edges) = path.edges.split_last().unwrap();
+        let prev_count = prev_edges
+            .iter()
+            .filter(|other| other.trip.eq(edge.trip))
+            .count();
+        bool_verdict(prev_count < self.capacities.get_trip(edge.trip))
+    }
+}
+
+struct LevelCapacity {
+    capacities: Arc<Capacities>,
+}
+impl LevelCapacity {
+    fn build(context: &Context) -> Box<dyn Constraint> {
+        Box::new(LevelCapacity {
+            capacities: context.capacities.clone(),
+        })
+    }
+}
+impl Constraint for LevelCapacity {
+    fn name(&self) -> &'static str {
+        "levelcap"
+    }
+    fn check(&self, path: &Path) -> Verdict {
+        let target = path.nodes.last().unwrap();
+        let edge = path.edges.last().unwrap();
+        let prev_count = path
+            .nodes
+            .iter()
+            .zip(path.edges.iter())
+            .filter(|(other_node, other_edge)| {
+                other_node.time_bin == target.time_bin
+                    && other_edge.trip.category == edge.trip.category
+            })
+            .count();
+        bool_verdict(
+            prev_count
+                < self
+                    .capacities
+                    .get_level(&edge.trip.category, target.time_bin),
+        )
+    }
+}
+
+struct ModeCapacity {
+    capacities: Arc<Capacities>,
+    counts: [usize; modes::COUNT],
+}
+impl ModeCapacity {
+    fn build(context: &Context) -> Box<dyn Constraint> {
+        Box::new(ModeCapacity {
+            capacities: context.capacities.clone(),
+            counts: [0; modes::COUNT],
+        })
+    }
+}
+impl Constraint for ModeCapacity {
+    fn name(&self) -> &'static str {
+        "modecap"
+    }
+    fn push(&mut self, path: &Path) {
+        self.counts[path.edges.last().unwrap().mode.index] += 1;
+    }
+    fn pop(&mut self, path: &Path) {
+        self.counts[path.edges.last().unwrap().mode.index] -= 1;
+    }
+    fn check(&self, path: &Path) -> Verdict {
+        let mode = path.edges.last().unwrap().mode;
+        bool_verdict(self.counts[mode.index] < self.capacities.get_mode(mode))
+    }
+}
@@ -0,0 +1,104 @@
+//! The inputs of a scenario, loaded from one directory.
+
+use super::categories::{self, Category};
+use super::districts::{self, District};
+use super::levels::{self, Levels};
+use super::modes::{self, Mode};
+use super::trips::{self, Demand, Loss, Scaling, Transport, Trip, TRANSPORTS};
+use std::path::Path;
+
+/// All inputs of a scenario. Everything built on a dataset borrows from it or
+/// refers to its elements by index, so several can be loaded side by side.
+pub struct Dataset {
+    /// How the trip counts were derived from the matrices.
+    pub scaling: Scaling,
+    /// Categories of `categoryInformation.txt`, in file order.
+    pub categories: Vec<Category>,
+    /// Districts of `qz-gebiet-nl.dat` with their coordinates in metres.
+    pub districts: Vec<District>,
+    /// Pegel per category, in the order of `categories`.
+    pub levels: Vec<Levels>,
+    /// Trips of all transports and categories with a scaled count of at least one.
+    pub trips: Vec<Trip>,
+    /// Modes with their target shares of all legs.
+    pub modes: Vec<Mode>,
+    /// Demand lost per matrix by integerizing it into `trips`.
+    pub losses: Vec<Loss>,
+    /// Scaled demand per matrix, before integerizing it.
+    demands: Vec<Demand>,
+}
+impl Dataset {
+    /// Reads the category information, districts, pegel and trip matrices
+    /// from `directory`, laid out like `verkehrsfluss/`, with the default
+    /// scaling. Fails with the path and reason for a missing or malformed file.
+    pub fn load<P: AsRef<Path>>(directory: P) -> Result<Self, String> {
+        Self::load_scaled(directory, &Scaling::DEFAULT)
+    }
+    /// As `load`, with the trip counts derived from the matrices by `scaling`.
+    pub fn load_scaled<P: AsRef<Path>>(directory: P, scaling: &Scaling) -> Result<Self, String> {
+        scaling.validate()?;
+        let directory = directory.as_ref();
+        let categories = categories::load(directory)?;
+        let districts = districts::load(directory)?;
+        let levels = levels::load(directory, &categories)?;
+        let mut demands: Vec<Demand> = Vec::new();
+        for &transport in TRANSPORTS.iter() {
+            for category in categories.iter() {
+                demands.push(trips::read_demand(
+                    directory,
+                    &districts,
+                    transport,
+                    category,
+                    scaling.factor,
+                )?);
+            }
+        }
+        let (trips, losses) = trips::load(&categories, &demands, scaling);
+        Ok(Dataset {
+            scaling: *scaling,
+            categories,
+            districts,
+            levels,
+            trips,
+            modes: modes::load(),
+            losses,
+            demands,
+        })
+    }
+    /// Pegel of a category.
+    pub fn levels_of(&self, category: &Category) -> &Levels {
+        &self.levels[category.index]
+    }
+    /// District a trip starts at.
+    pub fn origin(&self, trip: &Trip) -> &District {
+        &self.districts[trip.origin_index]
+    }
+    /// District a trip ends at.
+    pub fn destination(&self, trip: &Trip) -> &District {
+        &self.districts[trip.destination_index]
+    }
+    /// What was loaded and how much demand integerizing the matrices lost.
+    pub fn summary(&self) -> String {
+        format!(
+            "Loaded {} categories, {} districts, {} levels, {} distinct trips of {} total count; lost {:.1} of {:.1} demand, dropped {} of {} pairs.",
+            self.categories.len(),
+            self.districts.len(),
+            self.levels.len(),
+            self.trips.len(),
+            self.trip_total(),
+            self.losses.iter().map(Loss::lost).sum::<f64>(),
+            self.losses.iter().map(|loss| loss.demand).sum::<f64>(),
+            self.losses.iter().map(|loss| loss.dropped_pairs).sum::<usize>(),
+            self.losses.iter().map(|loss| loss.pairs).sum::<usize>()
+        )
+    }
+    /// Total count of all trips.
+    pub fn trip_total(&self) -> usize {
+        self.trips.iter().map(|trip| trip.count).sum()
+    }
+    /// Scaled but unrounded trip counts of the input matrix of a transport and
+    /// category, with the indices of their origin and destination districts.
+    pub fn demand(&self, transport: Transport, category: &Category) -> &[(usize, usize, f64)] {
+        &self.demands[trips::matrix_index(transport, category, self.categories.len())]
+    }
+}
@@ -1,18 +1,33 @@
+//! Traffic districts of `qz-gebiet-nl.dat` with the coordinates of their centres.
+
 use super::io;
-use lazy_static::lazy_static;
 use std::hash::{Hash, Hasher};
+use std::path::Path;
 
+/// Number of a district in the input files.
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub struct Id(u16);
+impl Id {
+    /// The number itself.
+    pub fn value(&self) -> u16 {
+        self.0
+    }
+}
 
+/// A traffic district, which trips start and end at.
 #[derive(Debug)]
+#[non_exhaustive]
 pub struct District {
+    /// Index in the districts of the dataset.
     pub index: usize,
+    /// Number in the input files.
     pub id: Id,
+    /// Easting of the centre in metres.
     pub x: f64,
+    /// Northing of the centre in metres.
     pub y: f64,
+    /// Name, followed by the name of its part of town if that differs.
     pub info: String,
-    _priv: (),
 }
 impl PartialEq for District {
     fn eq(&self, other: &Self) -> bool {
@@ -26,27 +41,27 @@ impl Hash for District {
     }
 }
 
-lazy_static! {
-    pub static ref DISTRICTS: Vec<District> = load();
-}
-
-pub fn parse_id(id: u16) -> Option<&'static District> {
-    DISTRICTS.iter().find(|&district| district.id.0 == id)
+/// District of an id among `districts`.
+pub fn parse_id(districts: &[District], id: u16) -> Option<&District> {
+    districts.iter().find(|&district| district.id.0 == id)
 }
 
-fn load() -> Vec<District> {
-    let records = io::read_csv(
-        "verkehrsfluss/verkehrsfluss-zusatz/qz-gebiet-nl.dat",
-        true,
-        false,
-        b'\t',
-        None,
-    );
+/// Districts of `qz-gebiet-nl.dat` with their coordinates in metres.
+pub(crate) fn load(directory: &Path) -> Result<Vec<District>, String> {
+    let path = directory.join("verkehrsfluss-zusatz/qz-gebiet-nl.dat");
+    let records = io::try_read_csv(&path, true, false, b'\t', None)?;
+    let in_file = |error: String| format!("{}: {}", path.display(), error);
     let mut vec: Vec<District> = Vec::new();
     for record in records {
-        let id = Id(record[0].parse().unwrap());
-        assert!(vec.iter().find(|&district| district.id.0 == id.0).is_none());
-        let (x, y) = (record[1].parse().unwrap(), record[2].parse().unwrap());
+        let id = Id(io::parse_field(&record, 0, "district id").map_err(in_file)?);
+        if vec.iter().any(|district| district.id.0 == id.0) {
+            return Err(in_file(format!("Duplicate district id {}!", id.0)));
+        }
+        let x = io::parse_field(&record, 1, "x coordinate").map_err(in_file)?;
+        let y = io::parse_field(&record, 2, "y coordinate").map_err(in_file)?;
+        if record.len() < 6 {
+            return Err(in_file(format!("Missing names of district {}!", id.0)));
+        }
         let info = compose_info(&record);
         vec.push(District {
             index: vec.len(),
@@ -54,11 +69,9 @@ fn load() -> Vec<District> {
             x,
             y,
             info,
-            _priv: (),
         });
     }
-    println!("Loaded {} districts.", vec.len());
-    vec
+    Ok(vec)
 }
 fn compose_info(record: &csv::StringRecord) -> String {
     if record[3].eq(&record[5]) {
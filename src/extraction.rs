@@ -0,0 +1,117 @@
+//! Choosing plans among the valid paths of a chunk within the capacities.
+
+use super::capacities::Capacities;
+use super::filters::PotentialPath;
+use super::ilp::{self, Row};
+use super::plans::Plan;
+use std::collections::HashMap;
+
+/// How the candidate paths found in a chunk are turned into plans.
+#[derive(Clone, Copy, Debug)]
+pub enum Extraction {
+    /// Repeat every path, in discovery order, until one of its capacities is exhausted.
+    Greedy,
+    /// Per batch of candidate paths, choose the multiplicities that consume the
+    /// most trips within trip, level and mode capacities by integer programming.
+    Optimal {
+        /// Candidate paths per integer program.
+        batch_size: usize,
+        /// Branch-and-bound nodes per integer program before the best
+        /// solution found so far is taken.
+        node_limit: usize,
+    },
+}
+
+impl Extraction {
+    /// Fails with the reason if the parameters cannot be used.
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Extraction::Optimal { batch_size: 0, .. } => {
+                Err("The batch size of optimal extraction must be positive!".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy)]
+enum Resource {
+    Trip(usize),
+    Level(usize, usize),
+    Mode(usize),
+}
+
+/// Appends the plans extracted from the candidate paths of a chunk to
+/// `plans` and consumes their capacities.
+pub(crate) fn extract<'a>(
+    extraction: Extraction,
+    stage: usize,
+    potential_paths: &[PotentialPath<'a>],
+    capacities: &mut Capacities,
+    plans: &mut Vec<Plan<'a>>,
+) {
+    let mut push = |potential_path: &PotentialPath<'a>| {
+        plans.push(Plan {
+            stage,
+            legs: potential_path.legs(),
+        })
+    };
+    match extraction {
+        Extraction::Greedy => {
+            for potential_path in potential_paths {
+                while potential_path.try_extracting(capacities).is_ok() {
+                    push(potential_path);
+                }
+            }
+        }
+        Extraction::Optimal {
+            batch_size,
+            node_limit,
+        } => {
+            for batch in potential_paths.chunks(batch_size) {
+                let multiplicities = solve(batch, capacities, node_limit);
+                for (potential_path, &count) in batch.iter().zip(multiplicities.iter()) {
+                    for _ in 0..count {
+                        // the capacities decide, should the solver have rounded past them
+                        if potential_path.try_extracting(capacities).is_err() {
+                            break;
+                        }
+                        push(potential_path);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn solve(batch: &[PotentialPath], capacities: &Capacities, node_limit: usize) -> Vec<u64> {
+    let mut resources: HashMap<Resource, usize> = HashMap::new();
+    let mut rows: Vec<Row> = Vec::new();
+    let mut limits: Vec<f64> = Vec::new();
+    for (j, potential_path) in batch.iter().enumerate() {
+        let usage = potential_path.usage();
+        let mut entries: Vec<(Resource, usize, usize)> = Vec::new();
+        for (trip, &count) in usage.trips.iter() {
+            entries.push((Resource::Trip(trip.index), capacities.get_trip(trip), count));
+        }
+        for ((category, time_bin), &count) in usage.levels.iter() {
+            let resource = Resource::Level(category.index, time_bin.value());
+            entries.push((resource, capacities.get_level(category, *time_bin), count));
+        }
+        for (mode, &count) in usage.modes.iter() {
+            entries.push((Resource::Mode(mode.index), capacities.get_mode(mode), count));
+        }
+        // rows in a fixed order, so that ties between optima are broken the same way every run
+        entries.sort_unstable();
+        for (resource, limit, count) in entries {
+            let i = *resources.entry(resource).or_insert_with(|| {
+                rows.push(Vec::new());
+                limits.push(limit as f64);
+                rows.len() - 1
+            });
+            rows[i].push((j, count as f64));
+        }
+    }
+    let objective: Vec<f64> = batch.iter().map(|path| path.len() as f64).collect();
+    ilp::maximize(&objective, &rows, &limits, node_limit)
+}
@@ -1,53 +1,92 @@
+//! Which paths through the graph are valid plans.
+
 use super::capacities::Capacities;
+use super::categories::Category;
+use super::constraints::{self, Constraint, Constructor, Context, Path, Rejections, Verdict};
 use super::graph::{Edge, Node};
+use super::modes::Mode;
 use super::purposes::Purpose;
-use super::time_bins;
+use super::time_bins::TimeBin;
+use super::trips::Trip;
 use itertools::Itertools;
-use std::ops::{Deref};
-use std::sync::Arc;
+use std::collections::HashMap;
 
-#[derive(Clone, Copy)]
+/// Which paths through the graph are valid plans.
+#[derive(Clone, Copy, Debug)]
 pub struct FilterParams {
+    /// Bounds on the number of activities, counting the first one; see the
+    /// "length" constraint.
     pub length_range: (usize, usize),
+    /// Purposes a plan may start with.
     pub first_activity: &'static [Purpose],
+    /// Least number of time bins a plan spans.
     pub duration_min: u8,
+    /// Whether a plan must end with the purpose it starts with.
     pub cycle: bool,
+    /// Constraints checked in this order; the built-in ones are constants
+    /// of `constraints`.
+    pub constraints: &'static [Constructor],
 }
-pub struct Filter {
-    params: FilterParams,
+/// Constraints of a depth-first search from one start node, checked
+/// against the path it is currently at.
+pub struct Filter<'a> {
     nodes: Vec<Node>,
-    edges: Vec<Edge>,
-    pub capacities: Arc<Capacities>,
-}
-impl Deref for Filter {
-    type Target = FilterParams;
-    fn deref(&self) -> &Self::Target {
-        &self.params
-    }
+    edges: Vec<Edge<'a>>,
+    constraints: Vec<Box<dyn Constraint>>,
+    /// Branches the constraints pruned so far.
+    pub rejections: Rejections,
 }
 
-impl Filter {
-    pub fn new(params: FilterParams, node: Node, capacities: Arc<Capacities>) -> Result<Self, ()> {
-        if !params.first_activity.contains(&node.purpose) {
+#[allow(clippy::wrong_self_convention)]
+impl<'a> Filter<'a> {
+    pub(crate) fn new(node: Node, context: &Context) -> Result<Self, ()> {
+        Self::with_constraints(node, context, context.params.constraints)
+    }
+    /// Like `new`, but checks the given constraints instead of those of the params.
+    pub(crate) fn with_constraints(
+        node: Node,
+        context: &Context,
+        constructors: &[Constructor],
+    ) -> Result<Self, ()> {
+        if !context.params.first_activity.contains(&node.purpose) {
             return Err(());
         }
         Ok(Filter {
-            params,
             nodes: vec![node],
             edges: Vec::new(),
-            capacities,
+            constraints: constraints::build(constructors, context),
+            rejections: Rejections::default(),
         })
     }
 
-    pub fn to_parent(&mut self) {
+    /// Removes the last node and edge of the path.
+    pub(crate) fn to_parent(&mut self) {
+        let path = Path {
+            nodes: &self.nodes,
+            edges: &self.edges,
+        };
+        for constraint in self.constraints.iter_mut() {
+            constraint.pop(&path);
+        }
         assert!(self.edges.pop().is_some());
         self.nodes.pop();
     }
 
-    pub fn to_child(&mut self, target: &Node, edge: &Edge) -> Result<Option<PotentialPath>, ()> {
+    pub(crate) fn to_child(
+        &mut self,
+        target: &Node,
+        edge: &Edge<'a>,
+    ) -> Result<Option<PotentialPath<'a>>, ()> {
         self.nodes.push(*target);
         self.edges.push(*edge);
-        match self.check(target, edge) {
+        let path = Path {
+            nodes: &self.nodes,
+            edges: &self.edges,
+        };
+        for constraint in self.constraints.iter_mut() {
+            constraint.push(&path);
+        }
+        match self.check() {
             Ok(true) => Ok(Some(PotentialPath {
                 nodes: self.nodes.clone(),
                 edges: self.edges.clone(),
@@ -56,117 +95,87 @@ impl Filter {
             Err(()) => Err(()),
         }
     }
-    fn check(&self, target: &Node, edge: &Edge) -> Result<bool, ()> {
+    fn check(&mut self) -> Result<bool, ()> {
+        let path = Path {
+            nodes: &self.nodes,
+            edges: &self.edges,
+        };
         let mut is_valid_path = true;
-        macro_rules! check {
-            ($check:expr, $name:expr) => {
-                if $check.is_none() {
-                    is_valid_path = false;
-                } else if !$check.unwrap() {
-                    // println!($name);
+        for constraint in self.constraints.iter() {
+            match constraint.check(&path) {
+                Verdict::Prune => {
+                    self.rejections.record(constraint.name(), path.edges.len());
                     return Err(());
                 }
-            };
-        }
-        check!(self.check_length(), "length");
-        check!(self.check_duration(), "duration");
-        check!(self.check_activity_cycle(target), "cycle");
-        check!(self.check_trip_capacity(edge), "tripcap");
-        check!(self.check_level_capacity(target, edge), "levelcap");
-        check!(self.check_mode_capacity(edge), "modecap");
-        // if is_valid_path {
-        //     println!("valid");
-        // }
+                Verdict::Incomplete => is_valid_path = false,
+                Verdict::Valid => {}
+            }
+        }
         Ok(is_valid_path)
     }
-    fn check_length(&self) -> Option<bool> {
-        if self.nodes.len() < self.length_range.0 {
-            None
-        } else {
-            Some(self.nodes.len() < self.length_range.1)
+}
+
+/// A valid path, which may become one or more plans.
+pub struct PotentialPath<'a> {
+    nodes: Vec<Node>,
+    edges: Vec<Edge<'a>>,
+}
+/// Capacities one extraction of a path consumes.
+pub struct Usage<'a> {
+    /// Legs per trip.
+    pub trips: HashMap<&'a Trip, usize>,
+    /// Legs per category and time bin of departure.
+    pub levels: HashMap<(&'a Category, TimeBin), usize>,
+    /// Legs per mode.
+    pub modes: HashMap<&'a Mode, usize>,
+}
+impl<'a> PotentialPath<'a> {
+    /// Path of a plan, including the node its last leg arrives at.
+    pub fn from_plan(plan: &[(Node, Edge<'a>)]) -> Self {
+        let mut nodes: Vec<Node> = plan.iter().map(|(node, _)| *node).collect();
+        let (last_node, last_edge) = plan.last().unwrap();
+        nodes.push(last_edge.target(last_node));
+        PotentialPath {
+            nodes,
+            edges: plan.iter().map(|(_, edge)| *edge).collect(),
         }
     }
-    fn check_duration(&self) -> Option<bool> {
-        let duration: u8 = self
-            .nodes
-            .iter()
-            .zip(self.nodes.iter().skip(1))
-            .map(|(first, second)| second.time_bin - first.time_bin)
-            .sum();
-        if duration > time_bins::COUNT as u8 {
-            return Some(false);
-        }
-        if duration < self.duration_min {
-            None
-        } else {
-            Some(true)
-        }
+    pub(crate) fn len(&self) -> usize {
+        self.edges.len()
     }
-    fn check_activity_cycle(&self, target: &Node) -> Option<bool> {
-        if !self.cycle || target.purpose.eq(&self.nodes.first().unwrap().purpose) {
-            Some(true)
-        } else {
-            None
+    /// Capacities one extraction of the path consumes.
+    pub fn usage(&self) -> Usage<'a> {
+        Usage {
+            trips: self.edges.iter().map(|edge| edge.trip).counts(),
+            levels: self
+                .edges
+                .iter()
+                .map(|edge| &edge.trip.category)
+                .zip(self.nodes.iter().map(|node| node.time_bin))
+                .counts(),
+            modes: self.edges.iter().map(|edge| edge.mode).counts(),
         }
     }
-    fn check_trip_capacity(&self, edge: &Edge) -> Option<bool> {
-        let prev_count = self
-            .edges
-            .iter()
-            .filter(|other| other.trip.eq(edge.trip))
-            .count();
-        Some(prev_count < self.capacities.get_trip(edge.trip))
-    }
-    fn check_level_capacity(&self, target: &Node, edge: &Edge) -> Option<bool> {
-        let prev_count = self
-            .nodes
-            .iter()
-            .zip(self.edges.iter())
-            .filter(|(other_node, other_edge)| {
-                other_node.time_bin == target.time_bin
-                    && other_edge.trip.category == edge.trip.category
-            })
-            .count();
-        Some(
-            prev_count
-                < self
-                    .capacities
-                    .get_level(edge.trip.category, target.time_bin),
-        )
-    }
-    fn check_mode_capacity(&self, edge: &Edge) -> Option<bool> {
-        let prev_count = self
-            .edges
+    /// Legs of the path, each with the node it departs from.
+    pub fn legs(&self) -> Vec<(Node, Edge<'a>)> {
+        self.nodes
             .iter()
-            .filter(|other| other.mode.eq(edge.mode))
-            .count();
-        Some(prev_count < self.capacities.get_mode(edge.mode))
+            .copied()
+            .zip(self.edges.iter().copied())
+            .collect()
     }
-}
-
-pub struct PotentialPath {
-    nodes: Vec<Node>,
-    edges: Vec<Edge>,
-}
-impl PotentialPath {
-    pub fn try_extracting(
-        &self,
-        capacities: &mut Capacities,
-        plans: &mut Vec<Vec<(Node, Edge)>>,
-    ) -> Result<(), ()> {
+    pub(crate) fn try_extracting(&self, capacities: &mut Capacities) -> Result<(), ()> {
+        let Usage {
+            trips: trip_usage,
+            levels: level_usage,
+            modes: mode_usage,
+        } = self.usage();
         // check
-        let trip_usage = self.edges.iter().map(|edge| edge.trip).counts();
         for trip in trip_usage.keys() {
             if *trip_usage.get(trip).unwrap() > capacities.get_trip(trip) {
                 return Err(());
             }
         }
-        let level_usage = self
-            .edges
-            .iter()
-            .map(|edge| edge.trip.category)
-            .zip(self.nodes.iter().map(|node| node.time_bin))
-            .counts();
         for (category, time_bin) in level_usage.keys() {
             if *level_usage.get(&(category, *time_bin)).unwrap()
                 > capacities.get_level(category, *time_bin)
@@ -174,7 +183,6 @@ impl PotentialPath {
                 return Err(());
             }
         }
-        let mode_usage = self.edges.iter().map(|edge| edge.mode).counts();
         for mode in mode_usage.keys() {
             if *mode_usage.get(mode).unwrap() > capacities.get_mode(mode) {
                 return Err(());
@@ -194,13 +202,19 @@ impl PotentialPath {
         for mode in mode_usage.keys() {
             capacities.reduce_mode(mode, *mode_usage.get(mode).unwrap());
         }
-        plans.push(
-            self.nodes
-                .iter()
-                .copied()
-                .zip(self.edges.iter().copied())
-                .collect(),
-        );
         Ok(())
     }
+    /// Inverse of a successful `try_extracting`.
+    pub fn release(&self, capacities: &mut Capacities) {
+        let usage = self.usage();
+        for (trip, count) in usage.trips {
+            capacities.release_trip(trip, count);
+        }
+        for ((category, time_bin), count) in usage.levels {
+            capacities.release_level(category, time_bin, count);
+        }
+        for (mode, count) in usage.modes {
+            capacities.release_mode(mode, count);
+        }
+    }
 }
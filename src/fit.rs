@@ -0,0 +1,170 @@
+//! Fit of the OD matrices of the plans with the input matrices.
+
+use super::dataset::Dataset;
+use super::io;
+use super::plans::Plan;
+use super::trips::TRANSPORTS;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+
+const WORST_PAIRS: usize = 20;
+/// GEH below which a flow is commonly considered to fit.
+const GEH_THRESHOLD: f64 = 5f64;
+const HEADER: [&str; 8] = [
+    "category",
+    "pairs",
+    "target",
+    "realized",
+    "coverage",
+    "rmse",
+    "mean_geh",
+    "geh_below_5",
+];
+const WORST_HEADER: [&str; 6] = [
+    "category",
+    "origin",
+    "destination",
+    "target",
+    "realized",
+    "geh",
+];
+
+/// Category, origin and destination district indices.
+type Pair = (usize, usize, usize);
+
+#[derive(Default)]
+struct Fit {
+    pairs: usize,
+    target: f64,
+    realized: f64,
+    squared_error: f64,
+    geh_sum: f64,
+    geh_below: usize,
+}
+impl Fit {
+    fn add(&mut self, target: f64, realized: f64) {
+        let geh = geh(target, realized);
+        self.pairs += 1;
+        self.target += target;
+        self.realized += realized;
+        self.squared_error += (realized - target).powi(2);
+        self.geh_sum += geh;
+        if geh < GEH_THRESHOLD {
+            self.geh_below += 1;
+        }
+    }
+    fn coverage(&self) -> f64 {
+        self.realized / self.target
+    }
+    fn rmse(&self) -> f64 {
+        (self.squared_error / self.pairs as f64).sqrt()
+    }
+    fn mean_geh(&self) -> f64 {
+        self.geh_sum / self.pairs as f64
+    }
+    fn geh_share(&self) -> f64 {
+        self.geh_below as f64 / self.pairs as f64
+    }
+    fn to_record(&self, category: String) -> Vec<String> {
+        vec![
+            category,
+            self.pairs.to_string(),
+            format!("{:.2}", self.target),
+            format!("{:.0}", self.realized),
+            format!("{:.4}", self.coverage()),
+            format!("{:.4}", self.rmse()),
+            format!("{:.4}", self.mean_geh()),
+            format!("{:.4}", self.geh_share()),
+        ]
+    }
+}
+
+fn geh(target: f64, realized: f64) -> f64 {
+    if target + realized == 0f64 {
+        0f64
+    } else {
+        (2f64 * (realized - target).powi(2) / (realized + target)).sqrt()
+    }
+}
+
+/// Compares the OD matrices of the plan legs with the input matrices, summed
+/// over transports: per category and in total, and for the worst OD pairs by GEH.
+/// Returns the total fit as a summary line.
+pub fn write<S: AsRef<OsStr>>(path: S, worst_path: S, dataset: &Dataset, plans: &[Plan]) -> String {
+    let mut flows: HashMap<Pair, (f64, f64)> = HashMap::new();
+    for &transport in TRANSPORTS.iter() {
+        for category in dataset.categories.iter() {
+            for &(origin, destination, demand) in dataset.demand(transport, category) {
+                flows
+                    .entry((category.index, origin, destination))
+                    .or_default()
+                    .0 += demand;
+            }
+        }
+    }
+    for (_, edge) in plans.iter().flat_map(|plan| plan.legs.iter()) {
+        let trip = edge.trip;
+        flows
+            .entry((
+                trip.category.index,
+                trip.origin_index,
+                trip.destination_index,
+            ))
+            .or_default()
+            .1 += 1f64;
+    }
+
+    let mut total = Fit::default();
+    let categories = &dataset.categories;
+    let mut by_category: Vec<Fit> = categories.iter().map(|_| Fit::default()).collect();
+    for (&(category_index, _, _), &(target, realized)) in flows.iter() {
+        total.add(target, realized);
+        by_category[category_index].add(target, realized);
+    }
+    let mut records: Vec<Vec<String>> = vec![HEADER.iter().map(|s| s.to_string()).collect()];
+    for (category, fit) in categories.iter().zip(by_category.iter()) {
+        if fit.pairs > 0 {
+            records.push(fit.to_record(category.id.value().to_string()));
+        }
+    }
+    records.push(total.to_record("total".to_string()));
+    io::write_csv(path, b';', &records);
+
+    let mut worst: Vec<(&Pair, f64, f64, f64)> = flows
+        .iter()
+        .map(|(pair, &(target, realized))| (pair, target, realized, geh(target, realized)))
+        .collect();
+    let district_id = |index: usize| dataset.districts[index].id.value();
+    // ties by OD pair, so that the file is the same every run
+    worst.sort_by(|a, b| {
+        let key = |(pair, ..): &(&Pair, f64, f64, f64)| {
+            (pair.0, district_id(pair.1), district_id(pair.2))
+        };
+        b.3.partial_cmp(&a.3).unwrap().then(key(a).cmp(&key(b)))
+    });
+    let mut worst_records: Vec<Vec<String>> =
+        vec![WORST_HEADER.iter().map(|s| s.to_string()).collect()];
+    for ((category_index, origin, destination), target, realized, geh) in
+        worst.into_iter().take(WORST_PAIRS)
+    {
+        worst_records.push(vec![
+            categories[*category_index].id.value().to_string(),
+            district_id(*origin).to_string(),
+            district_id(*destination).to_string(),
+            format!("{:.2}", target),
+            format!("{:.0}", realized),
+            format!("{:.4}", geh),
+        ]);
+    }
+    io::write_csv(worst_path, b';', &worst_records);
+
+    format!(
+        "OD fit: coverage {:.1}% | RMSE {:.3} | mean GEH {:.3} | GEH < {}: {:.1}% of {} pairs",
+        100f64 * total.coverage(),
+        total.rmse(),
+        total.mean_geh(),
+        GEH_THRESHOLD,
+        100f64 * total.geh_share(),
+        total.pairs,
+    )
+}
@@ -1,97 +1,207 @@
+//! The graph the search walks: activities at a district, purpose and time
+//! bin, connected by the trips between them.
+
 use super::capacities::Capacities;
-use super::districts;
-use super::modes::{Mode, MODES};
-use super::purposes::Purpose;
-use super::time_bins::{TimeBin, TIME_BINS};
-use super::trips::{Trip, TRIPS};
-use itertools::Itertools;
-use petgraph::graph::{EdgeIndex, Graph as Petgraph, NodeIndex};
-use petgraph::Direction::Outgoing;
-use std::collections::HashMap;
+use super::dataset::Dataset;
+use super::modes::{self, Mode};
+use super::purposes::{self, Purpose, PURPOSES};
+use super::time_bins::{self, TimeBin, TIME_BINS};
+use super::trips::Trip;
 
-pub struct Graph(Petgraph<Node, Edge>);
+/// Implicit graph: outgoing edges of a node are derived on demand from the
+/// trips starting at its (district, purpose), crossed with all modes.
+pub struct Graph<'a> {
+    modes: &'a [Mode],
+    nodes: Vec<Node>,
+    node_indices: Vec<NodeIndex>,
+    trips: Vec<Vec<&'a Trip>>,
+}
+/// An activity: a purpose pursued at a district from a time bin on.
 #[derive(PartialEq, Eq, Hash, Clone, Copy)]
 pub struct Node {
-    pub district_id: districts::Id,
+    /// Index of the district in the dataset.
+    pub district_index: usize,
+    /// Purpose of the activity.
     pub purpose: Purpose,
+    /// Time bin the activity starts in.
     pub time_bin: TimeBin,
 }
+/// A leg: a trip to the next activity, taken by a mode.
 #[derive(Clone, Copy)]
-pub struct Edge {
-    pub trip: &'static Trip,
-    pub mode: &'static Mode,
+pub struct Edge<'a> {
+    /// Trip of the leg.
+    pub trip: &'a Trip,
+    /// Mode the trip is taken by.
+    pub mode: &'a Mode,
+}
+impl Edge<'_> {
+    /// Node reached by taking this edge from `source`.
+    pub fn target(&self, source: &Node) -> Node {
+        Node {
+            district_index: self.trip.destination_index,
+            purpose: self.trip.category.destination,
+            time_bin: source.time_bin + self.trip.category.origin.duration(), // TODO: leg duration
+        }
+    }
+}
+/// Position of a node among all nodes of the graph.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub struct NodeIndex(u32);
+impl NodeIndex {
+    /// The position itself.
+    pub fn index(&self) -> usize {
+        self.0 as usize
+    }
+}
+/// Outgoing edge of a node, by its trip and mode.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub struct EdgeIndex {
+    source: NodeIndex,
+    trip: u32,
+    mode: u8,
 }
 
-impl Graph {
-    pub fn new() -> Self {
-        let mut graph = Petgraph::<Node, Edge>::new();
-        let mut nodes = HashMap::<Node, NodeIndex>::new();
-        for ((trip, time_bin), mode) in TRIPS
-            .iter()
-            .cartesian_product(TIME_BINS.into_iter().copied())
-            .cartesian_product(MODES.iter())
-        {
-            let trip_category = trip.category;
-            let source_key = Node {
-                district_id: trip.origin.id,
-                purpose: trip_category.origin,
-                time_bin,
-            };
-            if !nodes.contains_key(&source_key) {
-                nodes.insert(source_key, graph.add_node(source_key));
-            }
-            let source_index: NodeIndex = *nodes.get(&source_key).unwrap();
+/// Index of the (district, purpose) of a node among those of all districts.
+pub(crate) fn key_index(district_index: usize, purpose: Purpose) -> usize {
+    district_index * purposes::COUNT + purpose.index()
+}
+fn node_index(key_index: usize, time_bin: TimeBin) -> NodeIndex {
+    NodeIndex((key_index * time_bins::COUNT + time_bin.value()) as u32)
+}
 
-            let destination_key = Node {
-                district_id: trip.destination.id,
-                purpose: trip_category.destination,
-                time_bin: time_bin + trip_category.origin.duration(), // TODO: leg duration
-            };
-            if !nodes.contains_key(&destination_key) {
-                nodes.insert(destination_key, graph.add_node(destination_key));
+impl<'a> Graph<'a> {
+    /// Nodes of every district, purpose and time bin of `dataset`.
+    pub fn new(dataset: &'a Dataset) -> Self {
+        let district_count = dataset.districts.len();
+        let mut nodes: Vec<Node> =
+            Vec::with_capacity(district_count * purposes::COUNT * time_bins::COUNT);
+        for district in dataset.districts.iter() {
+            for &purpose in PURPOSES.iter() {
+                for &time_bin in TIME_BINS.iter() {
+                    nodes.push(Node {
+                        district_index: district.index,
+                        purpose,
+                        time_bin,
+                    });
+                }
             }
-            let destination_index: NodeIndex = *nodes.get(&destination_key).unwrap();
-
-            let edge_key = Edge { trip, mode };
-            graph.add_edge(source_index, destination_index, edge_key);
         }
-        graph.shrink_to_fit();
-        println!(
-            "\rBuilt graph: {} nodes, {:.2e} edges",
-            graph.node_count(),
-            graph.edge_count()
-        );
-        Graph(graph)
+        let mut trips: Vec<Vec<&'a Trip>> = vec![Vec::new(); district_count * purposes::COUNT];
+        let mut is_used = vec![false; nodes.len()];
+        for trip in dataset.trips.iter() {
+            let source_key = key_index(trip.origin_index, trip.category.origin);
+            let destination_key = key_index(trip.destination_index, trip.category.destination);
+            trips[source_key].push(trip);
+            for &time_bin in TIME_BINS.iter() {
+                is_used[node_index(source_key, time_bin).index()] = true;
+                let target_bin = time_bin + trip.category.origin.duration(); // TODO: leg duration
+                is_used[node_index(destination_key, target_bin).index()] = true;
+            }
+        }
+        let node_indices: Vec<NodeIndex> = (0..nodes.len())
+            .filter(|&i| is_used[i])
+            .map(|i| NodeIndex(i as u32))
+            .collect();
+        Graph {
+            modes: &dataset.modes,
+            nodes,
+            node_indices,
+            trips,
+        }
+    }
+    pub(crate) fn node_count(&self) -> usize {
+        self.node_indices.len()
     }
+    pub(crate) fn edge_count(&self) -> usize {
+        self.trips.iter().map(|trips| trips.len()).sum::<usize>() * time_bins::COUNT * modes::COUNT
+    }
+    fn trips(&self, node_index: NodeIndex) -> &[&'a Trip] {
+        &self.trips[node_index.index() / time_bins::COUNT]
+    }
+    /// Indices of the nodes that some trip starts or ends at.
     pub fn node_indices(&self) -> Vec<NodeIndex> {
-        self.0.node_indices().collect()
+        self.node_indices.clone()
     }
+    /// Node at an index.
     pub fn node(&self, node_index: NodeIndex) -> &Node {
-        self.0.node_weight(node_index).unwrap()
+        &self.nodes[node_index.index()]
     }
-    pub fn edge(&self, edge_index: EdgeIndex) -> &Edge {
-        self.0.edge_weight(edge_index).unwrap()
+    /// Edge at an index.
+    pub fn edge(&self, edge_index: EdgeIndex) -> Edge<'a> {
+        Edge {
+            trip: self.trips(edge_index.source)[edge_index.trip as usize],
+            mode: &self.modes[edge_index.mode as usize],
+        }
     }
+    /// First outgoing edge of a node; the others follow by `next_edge`.
     pub fn first_edge(&self, node_index: NodeIndex) -> Option<EdgeIndex> {
-        self.0.first_edge(node_index, Outgoing)
+        if self.trips(node_index).is_empty() {
+            None
+        } else {
+            Some(EdgeIndex {
+                source: node_index,
+                trip: 0,
+                mode: 0,
+            })
+        }
     }
+    /// Outgoing edge of the same node after `edge_index`, if any.
     pub fn next_edge(&self, edge_index: EdgeIndex) -> Option<EdgeIndex> {
-        self.0.next_edge(edge_index, Outgoing)
+        if (edge_index.mode as usize) + 1 < modes::COUNT {
+            Some(EdgeIndex {
+                mode: edge_index.mode + 1,
+                ..edge_index
+            })
+        } else if (edge_index.trip as usize) + 1 < self.trips(edge_index.source).len() {
+            Some(EdgeIndex {
+                trip: edge_index.trip + 1,
+                mode: 0,
+                ..edge_index
+            })
+        } else {
+            None
+        }
     }
+    /// Index of the node an edge leads to.
     pub fn target_index(&self, edge_index: EdgeIndex) -> NodeIndex {
-        self.0.edge_endpoints(edge_index).unwrap().1
+        let trip = self.trips(edge_index.source)[edge_index.trip as usize];
+        let time_bin = self.node(edge_index.source).time_bin + trip.category.origin.duration(); // TODO: leg duration
+        node_index(
+            key_index(trip.destination_index, trip.category.destination),
+            time_bin,
+        )
     }
-    pub fn filter_edges(&mut self, capacities: &Capacities) {
-        self.0.filter_map(
-            |_, node| Some(node),
-            |_, edge| {
-                if capacities.get_trip(edge.trip) == 0 {
-                    None
-                } else {
-                    Some(edge)
-                }
-            },
-        );
-        self.0.shrink_to_fit_edges();
+    /// Number of (district, purpose) keys, used or not.
+    pub(crate) fn key_count(&self) -> usize {
+        self.trips.len()
+    }
+    /// Distinct keys that trips from each key lead to, with the time bins
+    /// the leg takes. Edges exist for every time bin, so these are all edges
+    /// up to the time bin they depart at.
+    pub(crate) fn key_edges(&self) -> Vec<Vec<(usize, u8)>> {
+        self.trips
+            .iter()
+            .map(|trips| {
+                let mut targets: Vec<(usize, u8)> = trips
+                    .iter()
+                    .map(|trip| {
+                        let bins = TIME_BINS[0] + trip.category.origin.duration(); // TODO: leg duration
+                        (
+                            key_index(trip.destination_index, trip.category.destination),
+                            bins.value() as u8,
+                        )
+                    })
+                    .collect();
+                targets.sort_unstable();
+                targets.dedup();
+                targets
+            })
+            .collect()
+    }
+    /// Drops the edges of trips without remaining capacity.
+    pub(crate) fn filter_edges(&mut self, capacities: &Capacities) {
+        for trips in self.trips.iter_mut() {
+            trips.retain(|trip| capacities.get_trip(trip) > 0);
+        }
     }
 }
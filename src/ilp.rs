@@ -0,0 +1,289 @@
+const EPSILON: f64 = 1e-9;
+const MAX_PIVOTS: usize = 100_000;
+
+/// Sparse row of a constraint matrix: (column, coefficient).
+pub type Row = Vec<(usize, f64)>;
+
+/// Maximizes `objective · x` over non-negative integer vectors `x` subject to
+/// `rows[i] · x <= limits[i]`, by branch and bound on the LP relaxation.
+///
+/// All coefficients and limits must be non-negative and every column must
+/// appear in some row, so the zero vector is feasible and the problem bounded.
+/// At most `node_limit` branch-and-bound nodes are explored; the best integer
+/// solution found until then is returned, starting from a greedy one.
+pub fn maximize(objective: &[f64], rows: &[Row], limits: &[f64], node_limit: usize) -> Vec<u64> {
+    let n = objective.len();
+    let mut upper = vec![u64::MAX; n];
+    for (row, &limit) in rows.iter().zip(limits.iter()) {
+        for &(j, a) in row.iter() {
+            if a > EPSILON {
+                upper[j] = upper[j].min((limit / a + EPSILON).floor() as u64);
+            }
+        }
+    }
+    assert!(upper.iter().all(|&u| u != u64::MAX), "Unbounded column!");
+
+    // rows that cannot bind even at the upper bounds are dropped
+    let (rows, limits): (Vec<&Row>, Vec<f64>) = rows
+        .iter()
+        .zip(limits.iter())
+        .filter(|(row, &limit)| {
+            row.iter().map(|&(j, a)| a * upper[j] as f64).sum::<f64>() > limit + EPSILON
+        })
+        .map(|(row, &limit)| (row, limit))
+        .unzip();
+
+    let value = |x: &[u64]| -> f64 { x.iter().zip(objective).map(|(&x, c)| x as f64 * c).sum() };
+    // rounding errors of the simplex must not make a solution exceed a limit
+    let is_feasible = |x: &[u64]| -> bool {
+        x.iter().zip(upper.iter()).all(|(x, upper)| x <= upper)
+            && rows.iter().zip(limits.iter()).all(|(row, &limit)| {
+                row.iter().map(|&(j, a)| a * x[j] as f64).sum::<f64>() <= limit + EPSILON
+            })
+    };
+    let mut best = greedy(&rows, &limits, &upper);
+    let mut best_value = value(&best);
+
+    let mut stack: Vec<(Vec<u64>, Vec<u64>)> = vec![(vec![0; n], upper.clone())];
+    let mut node_count = 0;
+    while let Some((lower, node_upper)) = stack.pop() {
+        if node_count >= node_limit {
+            break;
+        }
+        node_count += 1;
+
+        // substitute x = lower + y, so that all rows keep the form rows · y <= limits
+        let mut node_limits: Vec<f64> = limits.clone();
+        for (row, limit) in rows.iter().zip(node_limits.iter_mut()) {
+            *limit -= row.iter().map(|&(j, a)| a * lower[j] as f64).sum::<f64>();
+        }
+        if node_limits.iter().any(|&limit| limit < -EPSILON) {
+            continue;
+        }
+        let mut node_rows: Vec<Row> = rows.iter().map(|&row| row.clone()).collect();
+        // dropped rows only stay slack as long as every column keeps to its upper bound
+        for j in 0..n {
+            node_rows.push(vec![(j, 1f64)]);
+            node_limits.push((node_upper[j] - lower[j]) as f64);
+        }
+        let (lp_value, y) = simplex(objective, &node_rows, &node_limits);
+        let bound = value(&lower) + lp_value;
+        if (bound + 1e-6).floor() <= best_value {
+            continue;
+        }
+
+        let fractional = (0..n)
+            .map(|j| (j, y[j] - y[j].floor()))
+            .filter(|&(_, fraction)| fraction > 1e-6 && fraction < 1f64 - 1e-6)
+            .max_by(|(_, a), (_, b)| {
+                (0.5 - (a - 0.5).abs())
+                    .partial_cmp(&(0.5 - (b - 0.5).abs()))
+                    .unwrap()
+            });
+        match fractional {
+            None => {
+                let x: Vec<u64> = (0..n)
+                    .map(|j| lower[j] + (y[j] + 1e-6).floor() as u64)
+                    .collect();
+                let x_value = value(&x);
+                if x_value > best_value && is_feasible(&x) {
+                    best = x;
+                    best_value = x_value;
+                }
+            }
+            Some((j, _)) => {
+                let split = lower[j] + y[j].floor() as u64;
+                let mut down_upper = node_upper.clone();
+                down_upper[j] = split;
+                stack.push((lower.clone(), down_upper));
+                let mut up_lower = lower;
+                up_lower[j] = split + 1;
+                stack.push((up_lower, node_upper));
+            }
+        }
+    }
+    best
+}
+
+/// Takes every column as often as the remaining limits allow, in order.
+fn greedy(rows: &[&Row], limits: &[f64], upper: &[u64]) -> Vec<u64> {
+    let mut remaining = limits.to_vec();
+    let mut x: Vec<u64> = Vec::with_capacity(upper.len());
+    for (j, &column_upper) in upper.iter().enumerate() {
+        let coefficients: Vec<(usize, f64)> = rows
+            .iter()
+            .enumerate()
+            .filter_map(|(i, row)| {
+                row.iter()
+                    .find(|&&(column, _)| column == j)
+                    .map(|&(_, a)| (i, a))
+            })
+            .filter(|&(_, a)| a > EPSILON)
+            .collect();
+        let count = coefficients
+            .iter()
+            .map(|&(i, a)| (remaining[i] / a + EPSILON).floor() as u64)
+            .fold(column_upper, u64::min);
+        for &(i, a) in coefficients.iter() {
+            remaining[i] -= a * count as f64;
+        }
+        x.push(count);
+    }
+    x
+}
+
+/// Solves max `objective · y` subject to `rows · y <= limits`, `y >= 0` with a
+/// dense tableau and Bland's rule. Requires `limits >= 0`.
+fn simplex(objective: &[f64], rows: &[Row], limits: &[f64]) -> (f64, Vec<f64>) {
+    let n = objective.len();
+    let m = rows.len();
+    let width = n + m + 1;
+    let mut tableau = vec![vec![0f64; width]; m + 1];
+    for (i, row) in rows.iter().enumerate() {
+        for &(j, a) in row.iter() {
+            tableau[i][j] += a;
+        }
+        tableau[i][n + i] = 1f64;
+        tableau[i][width - 1] = limits[i].max(0f64);
+    }
+    for (j, &c) in objective.iter().enumerate() {
+        tableau[m][j] = -c;
+    }
+    let mut basis: Vec<usize> = (n..n + m).collect();
+
+    for _ in 0..MAX_PIVOTS {
+        let entering = match (0..width - 1).find(|&j| tableau[m][j] < -EPSILON) {
+            Some(j) => j,
+            None => break,
+        };
+        let mut leaving: Option<usize> = None;
+        for i in 0..m {
+            if tableau[i][entering] > EPSILON {
+                let ratio = tableau[i][width - 1] / tableau[i][entering];
+                leaving = match leaving {
+                    Some(l) => {
+                        let best = tableau[l][width - 1] / tableau[l][entering];
+                        if ratio < best - EPSILON || (ratio < best + EPSILON && basis[i] < basis[l])
+                        {
+                            Some(i)
+                        } else {
+                            Some(l)
+                        }
+                    }
+                    None => Some(i),
+                };
+            }
+        }
+        let leaving = leaving.expect("Unbounded linear program!");
+        let pivot = tableau[leaving][entering];
+        for value in tableau[leaving].iter_mut() {
+            *value /= pivot;
+        }
+        let pivot_row = tableau[leaving].clone();
+        for (i, row) in tableau.iter_mut().enumerate() {
+            if i != leaving {
+                let factor = row[entering];
+                if factor.abs() > EPSILON {
+                    for (value, pivot_value) in row.iter_mut().zip(pivot_row.iter()) {
+                        *value -= factor * pivot_value;
+                    }
+                }
+            }
+        }
+        basis[leaving] = entering;
+    }
+
+    let mut y = vec![0f64; n];
+    for (i, &column) in basis.iter().enumerate() {
+        if column < n {
+            y[column] = tableau[i][width - 1];
+        }
+    }
+    (tableau[m][width - 1], y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(objective: &[f64], x: &[u64]) -> f64 {
+        x.iter().zip(objective).map(|(&x, c)| x as f64 * c).sum()
+    }
+
+    fn assert_feasible(rows: &[Row], limits: &[f64], x: &[u64]) {
+        for (row, &limit) in rows.iter().zip(limits.iter()) {
+            let used: f64 = row.iter().map(|&(j, a)| a * x[j] as f64).sum();
+            assert!(used <= limit + EPSILON, "{:?} exceeds a limit", x);
+        }
+    }
+
+    /// 0-1 knapsack whose greedy solution in column order is not optimal.
+    fn knapsack() -> (Vec<f64>, Vec<Row>, Vec<f64>) {
+        let objective = vec![8f64, 11f64, 6f64, 4f64];
+        let mut rows: Vec<Row> = vec![vec![(0, 5f64), (1, 7f64), (2, 4f64), (3, 3f64)]];
+        let mut limits = vec![14f64];
+        for j in 0..objective.len() {
+            rows.push(vec![(j, 1f64)]);
+            limits.push(1f64);
+        }
+        (objective, rows, limits)
+    }
+
+    #[test]
+    fn simplex_solves_textbook_program() {
+        let rows: Vec<Row> = vec![vec![(0, 1f64)], vec![(1, 2f64)], vec![(0, 3f64), (1, 2f64)]];
+        let (lp_value, y) = simplex(&[3f64, 5f64], &rows, &[4f64, 12f64, 18f64]);
+        assert!((lp_value - 36f64).abs() < 1e-6);
+        assert!((y[0] - 2f64).abs() < 1e-6 && (y[1] - 6f64).abs() < 1e-6);
+    }
+
+    #[test]
+    fn simplex_relaxes_knapsack() {
+        let (objective, rows, limits) = knapsack();
+        let (lp_value, _) = simplex(&objective, &rows, &limits);
+        assert!((lp_value - 22f64).abs() < 1e-6);
+    }
+
+    #[test]
+    fn branch_and_bound_beats_greedy() {
+        let (objective, rows, limits) = knapsack();
+        let x = maximize(&objective, &rows, &limits, 1000);
+        assert_eq!(x, vec![0, 1, 1, 1]);
+        assert_eq!(value(&objective, &x), 21f64);
+        assert_feasible(&rows, &limits, &x);
+    }
+
+    #[test]
+    fn node_limit_keeps_greedy_solution() {
+        let (objective, rows, limits) = knapsack();
+        let x = maximize(&objective, &rows, &limits, 0);
+        assert_eq!(x, vec![1, 1, 0, 0]);
+        assert_feasible(&rows, &limits, &x);
+    }
+
+    #[test]
+    fn shared_rows_bound_integer_multiplicities() {
+        // two paths sharing a trip of count 3, the second also limited by a level of 1
+        let objective = vec![2f64, 3f64];
+        let rows: Vec<Row> = vec![vec![(0, 1f64), (1, 1f64)], vec![(1, 1f64)]];
+        let limits = vec![3f64, 1f64];
+        let x = maximize(&objective, &rows, &limits, 100);
+        assert_eq!(x, vec![2, 1]);
+        assert_feasible(&rows, &limits, &x);
+    }
+
+    #[test]
+    fn non_integral_relaxation_stays_feasible() {
+        let objective = vec![1f64, 1f64, 1f64];
+        let rows: Vec<Row> = vec![
+            vec![(0, 2f64), (1, 2f64)],
+            vec![(1, 2f64), (2, 2f64)],
+            vec![(0, 2f64), (2, 2f64)],
+        ];
+        let limits = vec![3f64, 3f64, 3f64];
+        let x = maximize(&objective, &rows, &limits, 100);
+        assert_feasible(&rows, &limits, &x);
+        // the relaxation reaches 2.25, but any two columns together exceed a limit
+        assert_eq!(value(&objective, &x), 1f64);
+    }
+}
@@ -0,0 +1,498 @@
+//! Improving a set of plans by simulated annealing after the search.
+
+use super::capacities::Capacities;
+use super::constraints::{self, Constructor, Context};
+use super::dataset::Dataset;
+use super::filters::{Filter, FilterParams, PotentialPath};
+use super::graph::{Edge, Node};
+use super::modes::Mode;
+use super::plans::{self, Plan};
+use super::purposes::Purpose;
+use super::reachability::Reachability;
+use super::time_bins::TimeBin;
+use super::trips::Trip;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use std::mem;
+use std::sync::Arc;
+
+/// Attempts at drawing a second plan that fits the first one of a move.
+const MATCH_ATTEMPTS: usize = 32;
+
+/// Simulated annealing over the plans found by the search.
+#[derive(Clone, Copy, Debug)]
+pub struct Annealing {
+    /// Moves attempted.
+    pub iterations: u64,
+    /// Temperatures at the first and the last iteration, decreasing geometrically.
+    /// A move losing `d` trips is accepted with probability exp(-d / temperature).
+    pub temperatures: (f64, f64),
+    /// Seed of the random moves, so that runs can be repeated.
+    pub seed: u64,
+}
+
+type Legs<'a> = Vec<(Node, Edge<'a>)>;
+/// Indices of the plans a move replaces, and their replacements.
+type Move<'a> = (Vec<usize>, Vec<Legs<'a>>);
+
+/// A change to the plan set with what it replaced: the plan and the stage
+/// whose filter it has to pass.
+enum Change<'a> {
+    Set(usize, Plan<'a>, usize),
+    SwapRemove(usize, Plan<'a>, usize),
+    Push,
+}
+
+struct Improver<'a> {
+    /// Context and constraints without capacities, per stage.
+    stages: Vec<(Context, Vec<Constructor>)>,
+    modes: &'a [Mode],
+    trips: HashMap<(usize, Purpose), Vec<&'a Trip>>,
+    rng: StdRng,
+}
+
+/// Swaps, splits and joins legs between plans and inserts unused trips into
+/// them, as long as the capacities hold and every plan stays valid under the
+/// filter of the stage it was found by; only plans of the same stage are
+/// combined. Keeps the plans that cover the most trips and returns the
+/// number of accepted moves.
+pub fn improve<'a>(
+    dataset: &'a Dataset,
+    annealing: &Annealing,
+    stages: &[FilterParams],
+    reachability: Arc<Reachability>,
+    capacities: &mut Capacities,
+    plans: &mut Vec<Plan<'a>>,
+) -> usize {
+    if stages.is_empty() {
+        return 0;
+    }
+    let mut improver = Improver::new(dataset, stages, reachability, capacities, annealing.seed);
+    let mut origins: Vec<usize> = plans.iter().map(|plan| improver.origin(plan)).collect();
+    let mut used: usize = plans.iter().map(|plan| plan.legs.len()).sum();
+    // changes since the best plan set, undone if a worse one is left in the end
+    let mut journal: Vec<Change> = Vec::new();
+    let mut best_used = used;
+    let mut accepted = 0;
+    let (first_temperature, last_temperature) = annealing.temperatures;
+
+    for iteration in 0..annealing.iterations {
+        if plans.is_empty() {
+            break;
+        }
+        let progress = iteration as f64 / annealing.iterations as f64;
+        let temperature = first_temperature * (last_temperature / first_temperature).powf(progress);
+        let (old_indices, new_plans) = match improver.propose(plans, &origins, capacities) {
+            Some(proposal) => proposal,
+            None => continue,
+        };
+        let delta = trips_used(&new_plans) as i64
+            - old_indices
+                .iter()
+                .map(|&i| plans[i].legs.len())
+                .sum::<usize>() as i64;
+        if delta < 0 && improver.rng.gen::<f64>() >= (delta as f64 / temperature).exp() {
+            continue;
+        }
+
+        for &i in old_indices.iter() {
+            PotentialPath::from_plan(&plans[i].legs).release(capacities);
+        }
+        if !try_extracting(&new_plans, capacities) {
+            for &i in old_indices.iter() {
+                PotentialPath::from_plan(&plans[i].legs)
+                    .try_extracting(capacities)
+                    .unwrap();
+            }
+            continue;
+        }
+        let origin = origins[old_indices[0]];
+        replace(
+            plans,
+            &mut origins,
+            &mut journal,
+            old_indices,
+            new_plans,
+            origin,
+        );
+        accepted += 1;
+        used = (used as i64 + delta) as usize;
+        if used > best_used {
+            best_used = used;
+            journal.clear();
+        }
+    }
+    if used < best_used {
+        undo(plans, &mut origins, journal, capacities);
+    }
+    accepted
+}
+
+fn trips_used(plans: &[Legs]) -> usize {
+    plans.iter().map(|legs| legs.len()).sum()
+}
+
+/// Extracts all plans or, if one does not fit into the capacities, none.
+fn try_extracting(plans: &[Legs], capacities: &mut Capacities) -> bool {
+    let paths: Vec<PotentialPath> = plans
+        .iter()
+        .map(|plan| PotentialPath::from_plan(plan))
+        .collect();
+    for (i, path) in paths.iter().enumerate() {
+        if path.try_extracting(capacities).is_err() {
+            for extracted in paths[..i].iter() {
+                extracted.release(capacities);
+            }
+            return false;
+        }
+    }
+    true
+}
+
+/// Puts `new_plans`, which pass the filter of stage `origin`, in place of
+/// the plans at `old_indices`, and records the changes in `journal`.
+fn replace<'a>(
+    plans: &mut Vec<Plan<'a>>,
+    origins: &mut Vec<usize>,
+    journal: &mut Vec<Change<'a>>,
+    mut old_indices: Vec<usize>,
+    new_plans: Vec<Legs<'a>>,
+    origin: usize,
+) {
+    old_indices.sort_unstable();
+    let mut new_plans: Vec<Plan> = new_plans
+        .into_iter()
+        .map(|legs| Plan {
+            stage: plans::IMPROVED,
+            legs,
+        })
+        .collect();
+    while !old_indices.is_empty() && !new_plans.is_empty() {
+        let i = old_indices.remove(0);
+        let previous = mem::replace(&mut plans[i], new_plans.remove(0));
+        journal.push(Change::Set(
+            i,
+            previous,
+            mem::replace(&mut origins[i], origin),
+        ));
+    }
+    for &i in old_indices.iter().rev() {
+        let previous = plans.swap_remove(i);
+        journal.push(Change::SwapRemove(i, previous, origins.swap_remove(i)));
+    }
+    for plan in new_plans {
+        plans.push(plan);
+        origins.push(origin);
+        journal.push(Change::Push);
+    }
+}
+
+/// Reverts the changes of `journal`, latest first, with their capacities.
+fn undo<'a>(
+    plans: &mut Vec<Plan<'a>>,
+    origins: &mut Vec<usize>,
+    journal: Vec<Change<'a>>,
+    capacities: &mut Capacities,
+) {
+    let mut removed: Vec<Plan> = Vec::new();
+    let mut restored: Vec<Legs> = Vec::new();
+    for change in journal.into_iter().rev() {
+        match change {
+            Change::Set(i, previous, origin) => {
+                restored.push(previous.legs.clone());
+                removed.push(mem::replace(&mut plans[i], previous));
+                origins[i] = origin;
+            }
+            Change::SwapRemove(i, previous, origin) => {
+                restored.push(previous.legs.clone());
+                if i == plans.len() {
+                    plans.push(previous);
+                    origins.push(origin);
+                } else {
+                    let moved = mem::replace(&mut plans[i], previous);
+                    plans.push(moved);
+                    let moved_origin = mem::replace(&mut origins[i], origin);
+                    origins.push(moved_origin);
+                }
+            }
+            Change::Push => {
+                removed.push(plans.pop().unwrap());
+                origins.pop();
+            }
+        }
+    }
+    for plan in removed.iter() {
+        PotentialPath::from_plan(&plan.legs).release(capacities);
+    }
+    assert!(try_extracting(&restored, capacities));
+}
+
+fn key(node: &Node) -> (usize, Purpose) {
+    (node.district_index, node.purpose)
+}
+
+fn edges<'a>(plan: &[(Node, Edge<'a>)]) -> Vec<Edge<'a>> {
+    plan.iter().map(|(_, edge)| *edge).collect()
+}
+
+/// Plan taking `edges` one after another, starting at `time_bin`.
+fn chain<'a>(time_bin: TimeBin, edges: &[Edge<'a>]) -> Legs<'a> {
+    let mut node = Node {
+        district_index: edges[0].trip.origin_index,
+        purpose: edges[0].trip.category.origin,
+        time_bin,
+    };
+    edges
+        .iter()
+        .map(|edge| {
+            let leg = (node, *edge);
+            node = edge.target(&node);
+            leg
+        })
+        .collect()
+}
+
+impl<'a> Improver<'a> {
+    fn new(
+        dataset: &'a Dataset,
+        stages: &[FilterParams],
+        reachability: Arc<Reachability>,
+        capacities: &Capacities,
+        seed: u64,
+    ) -> Self {
+        let mut trips: HashMap<(usize, Purpose), Vec<&'a Trip>> = HashMap::new();
+        for trip in dataset.trips.iter() {
+            trips
+                .entry((trip.origin_index, trip.category.origin))
+                .or_default()
+                .push(trip);
+        }
+        // capacities are checked for the whole plan set instead of per plan
+        let capacities = Arc::new(capacities.clone());
+        let stages = stages
+            .iter()
+            .map(|filter_params| {
+                let context = Context {
+                    params: *filter_params,
+                    capacities: capacities.clone(),
+                    reachability: reachability.clone(),
+                };
+                let constraints = filter_params
+                    .constraints
+                    .iter()
+                    .filter(|constraint| !constraints::CAPACITY.contains(constraint))
+                    .copied()
+                    .collect();
+                (context, constraints)
+            })
+            .collect();
+        Improver {
+            stages,
+            modes: &dataset.modes,
+            trips,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Index of the stage whose filter a plan has to pass; the last one for
+    /// plans of no stage of this search.
+    fn origin(&self, plan: &Plan) -> usize {
+        if plan.stage == plans::IMPROVED || plan.stage > self.stages.len() {
+            self.stages.len() - 1
+        } else {
+            plan.stage - 1
+        }
+    }
+
+    fn propose(
+        &mut self,
+        plans: &[Plan<'a>],
+        origins: &[usize],
+        capacities: &Capacities,
+    ) -> Option<Move<'a>> {
+        let proposal = match self.rng.gen_range(0..6) {
+            0 => self.insert_detour(plans, capacities),
+            1 => self.bypass(plans, capacities),
+            2 => self.remove_detour(plans),
+            3 => self.swap_tails(plans, origins),
+            4 => self.split(plans),
+            _ => self.join(plans, origins),
+        };
+        proposal.filter(|(old_indices, new_plans)| {
+            let origin = origins[old_indices[0]];
+            new_plans.iter().all(|plan| self.is_valid(origin, plan))
+        })
+    }
+
+    fn is_valid(&self, origin: usize, plan: &[(Node, Edge)]) -> bool {
+        let (context, constraints) = &self.stages[origin];
+        let mut filter = match Filter::with_constraints(plan[0].0, context, constraints) {
+            Ok(filter) => filter,
+            Err(()) => return false,
+        };
+        let mut is_valid = false;
+        for (node, edge) in plan.iter() {
+            match filter.to_child(&edge.target(node), edge) {
+                Err(()) => return false,
+                Ok(potential_path) => is_valid = potential_path.is_some(),
+            }
+        }
+        is_valid
+    }
+
+    fn random_edge(&mut self, from: &Node, capacities: &Capacities) -> Option<Edge<'a>> {
+        let trips = self.trips.get(&key(from))?;
+        let trip = trips[self.rng.gen_range(0..trips.len())];
+        if capacities.get_trip(trip) == 0 {
+            return None;
+        }
+        Some(Edge {
+            trip,
+            mode: &self.modes[self.rng.gen_range(0..self.modes.len())],
+        })
+    }
+
+    fn connecting_edge(
+        &mut self,
+        from: &Node,
+        to: &Node,
+        capacities: &Capacities,
+    ) -> Option<Edge<'a>> {
+        let trips: Vec<&'a Trip> = self
+            .trips
+            .get(&key(from))?
+            .iter()
+            .filter(|trip| {
+                trip.destination_index == to.district_index
+                    && trip.category.destination == to.purpose
+                    && capacities.get_trip(trip) > 0
+            })
+            .copied()
+            .collect();
+        if trips.is_empty() {
+            return None;
+        }
+        Some(Edge {
+            trip: trips[self.rng.gen_range(0..trips.len())],
+            mode: &self.modes[self.rng.gen_range(0..self.modes.len())],
+        })
+    }
+
+    fn random_plan(&mut self, plans: &[Plan]) -> usize {
+        self.rng.gen_range(0..plans.len())
+    }
+
+    /// Inserts two unused trips leaving and returning to an activity of a plan.
+    fn insert_detour(&mut self, plans: &[Plan<'a>], capacities: &Capacities) -> Option<Move<'a>> {
+        let p = self.random_plan(plans);
+        let i = self.rng.gen_range(0..plans[p].legs.len());
+        let node = plans[p].legs[i].0;
+        let out = self.random_edge(&node, capacities)?;
+        let back = self.connecting_edge(&out.target(&node), &node, capacities)?;
+        let mut edges = edges(&plans[p].legs);
+        edges.splice(i..i, vec![out, back]);
+        Some((vec![p], vec![chain(plans[p].legs[0].0.time_bin, &edges)]))
+    }
+
+    /// Replaces a leg of a plan by two unused trips via another activity.
+    fn bypass(&mut self, plans: &[Plan<'a>], capacities: &Capacities) -> Option<Move<'a>> {
+        let p = self.random_plan(plans);
+        let i = self.rng.gen_range(0..plans[p].legs.len());
+        let (node, edge) = plans[p].legs[i];
+        let first = self.random_edge(&node, capacities)?;
+        let second = self.connecting_edge(&first.target(&node), &edge.target(&node), capacities)?;
+        let mut edges = edges(&plans[p].legs);
+        edges.splice(i..=i, vec![first, second]);
+        Some((vec![p], vec![chain(plans[p].legs[0].0.time_bin, &edges)]))
+    }
+
+    /// Removes two consecutive legs returning to the activity they left,
+    /// and with them the whole plan if nothing else is left of it.
+    fn remove_detour(&mut self, plans: &[Plan<'a>]) -> Option<Move<'a>> {
+        let p = self.random_plan(plans);
+        let plan = &plans[p].legs;
+        let positions: Vec<usize> = (0..plan.len().saturating_sub(1))
+            .filter(|&i| {
+                let (node, edge) = plan[i + 1];
+                key(&edge.target(&node)) == key(&plan[i].0)
+            })
+            .collect();
+        if positions.is_empty() {
+            return None;
+        }
+        let i = positions[self.rng.gen_range(0..positions.len())];
+        let mut edges = edges(plan);
+        edges.drain(i..i + 2);
+        if edges.is_empty() {
+            Some((vec![p], Vec::new()))
+        } else {
+            Some((vec![p], vec![chain(plan[0].0.time_bin, &edges)]))
+        }
+    }
+
+    /// Exchanges the remaining legs of two plans of a stage after a common activity.
+    fn swap_tails(&mut self, plans: &[Plan<'a>], origins: &[usize]) -> Option<Move<'a>> {
+        let p = self.random_plan(plans);
+        if plans[p].legs.len() < 2 {
+            return None;
+        }
+        let i = self.rng.gen_range(1..plans[p].legs.len());
+        let node = plans[p].legs[i].0;
+        for _ in 0..MATCH_ATTEMPTS {
+            let q = self.random_plan(plans);
+            if q == p || origins[q] != origins[p] {
+                continue;
+            }
+            let positions: Vec<usize> = (1..plans[q].legs.len())
+                .filter(|&j| key(&plans[q].legs[j].0) == key(&node))
+                .collect();
+            if positions.is_empty() {
+                continue;
+            }
+            let j = positions[self.rng.gen_range(0..positions.len())];
+            let (p_edges, q_edges) = (edges(&plans[p].legs), edges(&plans[q].legs));
+            let new_p = [&p_edges[..i], &q_edges[j..]].concat();
+            let new_q = [&q_edges[..j], &p_edges[i..]].concat();
+            return Some((
+                vec![p, q],
+                vec![
+                    chain(plans[p].legs[0].0.time_bin, &new_p),
+                    chain(plans[q].legs[0].0.time_bin, &new_q),
+                ],
+            ));
+        }
+        None
+    }
+
+    /// Splits a plan where it returns to its first activity.
+    fn split(&mut self, plans: &[Plan<'a>]) -> Option<Move<'a>> {
+        let p = self.random_plan(plans);
+        let plan = &plans[p].legs;
+        let positions: Vec<usize> = (1..plan.len())
+            .filter(|&i| key(&plan[i].0) == key(&plan[0].0))
+            .collect();
+        if positions.is_empty() {
+            return None;
+        }
+        let i = positions[self.rng.gen_range(0..positions.len())];
+        Some((vec![p], vec![plan[..i].to_vec(), plan[i..].to_vec()]))
+    }
+
+    /// Appends a plan to another one of its stage starting at the same activity.
+    fn join(&mut self, plans: &[Plan<'a>], origins: &[usize]) -> Option<Move<'a>> {
+        let p = self.random_plan(plans);
+        for _ in 0..MATCH_ATTEMPTS {
+            let q = self.random_plan(plans);
+            if q == p
+                || origins[q] != origins[p]
+                || key(&plans[q].legs[0].0) != key(&plans[p].legs[0].0)
+            {
+                continue;
+            }
+            let edges = [edges(&plans[p].legs), edges(&plans[q].legs)].concat();
+            return Some((vec![p, q], vec![chain(plans[p].legs[0].0.time_bin, &edges)]));
+        }
+        None
+    }
+}
@@ -1,40 +1,88 @@
-use csv::{ReaderBuilder, StringRecord};
+use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+use itertools::Itertools;
 use std::ffi::OsStr;
-use std::fs::File;
-use std::io::Read;
+use std::fs::{self, File};
+use std::io::{self, Read};
 use std::path::Path;
+use std::str::FromStr;
 
-pub fn read_csv<S: AsRef<OsStr>>(
+/// Records of a CSV file, failing with the path and the reason if the file cannot be read.
+pub fn try_read_csv<S: AsRef<OsStr>>(
     path: S,
     is_ascii: bool,
     has_headers: bool,
     delimiter: u8,
     comment: Option<u8>,
-) -> Vec<StringRecord> {
+) -> Result<Vec<StringRecord>, String> {
+    let path = Path::new(&path);
     let data = if is_ascii {
-        read_ascii_file(&Path::new(&path))
+        read_ascii_file(path)
     } else {
-        read_file(&Path::new(&path))
-    };
+        read_file(path)
+    }
+    .map_err(|error| format!("Cannot read \"{}\": {}", path.display(), error))?;
     let mut reader = ReaderBuilder::new()
         .has_headers(has_headers)
         .flexible(true)
         .delimiter(delimiter)
         .comment(comment)
         .from_reader(data.as_bytes());
-    reader.records().map(|result| result.unwrap()).collect()
+    reader
+        .records()
+        .collect::<Result<_, _>>()
+        .map_err(|error| format!("{}: {}", path.display(), error))
 }
 
-fn read_file(path: &Path) -> String {
-    let mut file = File::open(&path).unwrap();
+/// Field `column` of `record` parsed as `T`, or an error naming it as `name`.
+pub fn parse_field<T: FromStr>(
+    record: &StringRecord,
+    column: usize,
+    name: &str,
+) -> Result<T, String> {
+    let field = record
+        .get(column)
+        .ok_or_else(|| format!("Missing {} in \"{}\"!", name, record.iter().join(" ")))?;
+    field
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid {} \"{}\"!", name, field))
+}
+
+pub fn write_csv<S: AsRef<OsStr>>(path: S, delimiter: u8, records: &[Vec<String>]) {
+    let path = Path::new(&path);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).unwrap();
+    }
+    let mut writer = WriterBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delimiter)
+        .from_path(path)
+        .unwrap();
+    for record in records {
+        writer.write_record(record).unwrap();
+    }
+    writer.flush().unwrap();
+}
+
+pub fn write_text<S: AsRef<OsStr>>(path: S, text: &str) {
+    let path = Path::new(&path);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).unwrap();
+    }
+    fs::write(path, text).unwrap();
+}
+
+fn read_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
     let mut data: String = "".to_string();
-    file.read_to_string(&mut data).unwrap();
-    data
+    file.read_to_string(&mut data)?;
+    Ok(data)
 }
 
-fn read_ascii_file(path: &Path) -> String {
-    let mut file = File::open(&path).unwrap();
+fn read_ascii_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
     let mut data = Vec::new();
-    file.read_to_end(&mut data).unwrap();
-    String::from_utf8_lossy(&data).to_string()
+    file.read_to_end(&mut data)?;
+    Ok(String::from_utf8_lossy(&data).to_string())
 }
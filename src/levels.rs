@@ -1,36 +1,32 @@
-use super::categories::{Category, CATEGORIES};
+//! Pegel: the share of the trips of a category departing in each time bin.
+
+use super::categories::Category;
 use super::io;
 use super::time_bins;
-use lazy_static::lazy_static;
-
-type Levels = [f64; time_bins::COUNT];
+use std::path::Path;
 
-lazy_static! {
-    static ref LEVELS_VEC: Vec<Levels> = load();
-}
-
-pub fn get_levels(category: &Category) -> Levels {
-    LEVELS_VEC[category.index]
-}
+/// Pegel of a category: its relative number of departures per time bin.
+pub type Levels = [f64; time_bins::COUNT];
 
-fn load() -> Vec<Levels> {
+/// Pegel of `categories`, in their order.
+pub(crate) fn load(directory: &Path, categories: &[Category]) -> Result<Vec<Levels>, String> {
     let mut vec: Vec<Levels> = Vec::new();
-    for category in CATEGORIES.iter() {
-        let path = format!(
-            "verkehrsfluss/verkehrsflussdaten/pegel{}.txt",
+    for category in categories.iter() {
+        let path = directory.join(format!(
+            "verkehrsflussdaten/pegel{}.txt",
             category.id.value()
-        );
-        let record = &io::read_csv(path, true, false, b';', Some(b'/'))[0];
+        ));
+        let records = io::try_read_csv(&path, true, false, b';', Some(b'/'))?;
+        let in_file = |error: String| format!("{}: {}", path.display(), error);
+        let record = records
+            .first()
+            .ok_or_else(|| in_file("No levels!".to_string()))?;
         let mut values = [0f64; time_bins::COUNT];
-        for (i, entry) in record.iter().take(time_bins::COUNT).enumerate() {
-            values[i] = entry.parse().unwrap();
+        for (i, value) in values.iter_mut().enumerate() {
+            *value =
+                io::parse_field(record, i, &format!("level of time bin {}", i)).map_err(in_file)?;
         }
         vec.push(values);
     }
-    println!(
-        "Loaded {} levels, each for {} time bins.",
-        vec.len(),
-        time_bins::COUNT
-    );
-    vec
+    Ok(vec)
 }
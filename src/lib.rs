@@ -0,0 +1,46 @@
+//! Day plans from trip matrices: chains of trips that start and end at the
+//! same activity, found by a staged search over districts, purposes and time
+//! bins within the capacities of the trips, the pegel levels and the modes.
+//!
+//! A [`dataset::Dataset`] is loaded from a directory laid out like
+//! `verkehrsfluss/`; the modes are built in. [`search::search`] runs a
+//! [`search::Config`] on it; the plans of its [`search::Run`] borrow from the
+//! dataset and can be written with [`plans::write`] and evaluated with the
+//! statistics modules. Stages may check constraints of their own next to the
+//! built-in ones by listing a [`constraints::Constructor`] for them.
+
+#![warn(missing_docs)]
+
+pub mod capacities;
+pub mod categories;
+pub mod chains;
+mod checkpoint;
+pub mod comparison;
+pub mod constraints;
+pub mod dataset;
+pub mod districts;
+pub mod extraction;
+pub mod filters;
+pub mod fit;
+pub mod graph;
+mod ilp;
+pub mod improvement;
+mod io;
+pub mod levels;
+pub mod map;
+pub mod modal_split;
+pub mod modes;
+pub mod plans;
+pub mod profile;
+pub mod purposes;
+pub mod reachability;
+pub mod report;
+mod residuals;
+mod rounding;
+pub mod sankey;
+pub mod scoring;
+pub mod search;
+pub mod stopping;
+mod svg;
+pub mod time_bins;
+pub mod trips;
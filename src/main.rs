@@ -1,18 +1,212 @@
-mod capacities;
-mod categories;
-mod districts;
-mod filters;
-mod graph;
-mod io;
-mod levels;
-mod modes;
-mod purposes;
-mod sankey;
-mod search;
-mod time_bins;
-mod trips;
+use day_plans::dataset::Dataset;
+use day_plans::map::{self, MapFilter};
+use day_plans::plans::{self, Plan};
+use day_plans::purposes::PURPOSES;
+use day_plans::sankey::Sankey;
+use day_plans::search::{self, Config};
+use day_plans::trips::{self, Scaling};
+use day_plans::{chains, comparison, fit, modal_split, profile, report, time_bins};
+use std::env;
+use std::process;
+
+const DATASET_PATH: &str = "verkehrsfluss";
+
+/// Prints the reason of a usage error and exits.
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+/// Value following `name` among the arguments, if `name` is given.
+fn option<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter().position(|arg| arg == name).map(|i| {
+        args.get(i + 1)
+            .unwrap_or_else(|| fail(&format!("Missing value after {}!", name)))
+            .as_str()
+    })
+}
+
+/// Exits unless the arguments are a known command, the plan files it reads
+/// and options it accepts.
+fn check_usage(args: &[String]) {
+    const SCALING: &[&str] = &["--trip-factor", "--integerization"];
+    const ANALYSIS: &[&str] = &[
+        "--chain-reference",
+        "--map-purpose",
+        "--map-mode",
+        "--map-time-bins",
+    ];
+    let (command, usage, paths, flags, options) = match args.get(1).map(String::as_str) {
+        Some("compare") => (
+            1,
+            "Usage: day-plans compare <plans a> <plans b> [options]",
+            2,
+            &[][..],
+            SCALING.to_vec(),
+        ),
+        Some("analyze") => (
+            1,
+            "Usage: day-plans analyze <plans> [options]",
+            1,
+            &[][..],
+            [SCALING, ANALYSIS].concat(),
+        ),
+        Some(command) if !command.starts_with("--") => {
+            fail(&format!("Unknown command \"{}\"!", command))
+        }
+        _ => (
+            0,
+            "Usage: day-plans [--resume] [options]",
+            0,
+            &["--resume"][..],
+            [SCALING, ANALYSIS].concat(),
+        ),
+    };
+    let rest = &args[1 + command..];
+    if rest.len() < paths || rest[..paths].iter().any(|arg| arg.starts_with("--")) {
+        fail(usage);
+    }
+    let mut i = paths;
+    while i < rest.len() {
+        let arg = rest[i].as_str();
+        if flags.contains(&arg) {
+            i += 1;
+        } else if options.contains(&arg) {
+            i += 2;
+        } else {
+            fail(&format!("Unknown option \"{}\"!\n{}", arg, usage));
+        }
+    }
+}
 
 fn main() {
-    // sankey::main();
-    search::search();
+    let args: Vec<String> = env::args().collect();
+    check_usage(&args);
+    let scaling = Scaling {
+        factor: option(&args, "--trip-factor").map_or(Scaling::DEFAULT.factor, |factor| {
+            factor
+                .parse()
+                .unwrap_or_else(|_| fail("The trip factor must be a number!"))
+        }),
+        integerization: option(&args, "--integerization")
+            .map_or(Scaling::DEFAULT.integerization, |name| {
+                name.parse().unwrap_or_else(|error: String| fail(&error))
+            }),
+    };
+    let dataset = Dataset::load_scaled(DATASET_PATH, &scaling).unwrap_or_else(|error| fail(&error));
+    println!("{}", dataset.summary());
+    trips::write_losses("output/trip_losses.csv", &dataset.losses);
+    if args.get(1).map(String::as_str) == Some("compare") {
+        let summary = comparison::write(
+            "output/comparison.csv",
+            &dataset,
+            &read_plans(&args[2], &dataset),
+            &read_plans(&args[3], &dataset),
+        );
+        println!("{}", summary);
+        return;
+    }
+    let resume = args.iter().any(|arg| arg == "--resume");
+    let chain_reference = option(&args, "--chain-reference")
+        .map(|path| chains::read_reference(path).unwrap_or_else(|error| fail(&error)));
+    let map_filter = MapFilter {
+        purpose: option(&args, "--map-purpose").map(|name| {
+            *PURPOSES
+                .iter()
+                .find(|purpose| format!("{:?}", purpose) == name)
+                .unwrap_or_else(|| fail(&format!("Unknown purpose \"{}\"!", name)))
+        }),
+        mode: option(&args, "--map-mode").map(|name| {
+            dataset
+                .modes
+                .iter()
+                .find(|mode| mode.name == name)
+                .unwrap_or_else(|| fail(&format!("Unknown mode \"{}\"!", name)))
+        }),
+        time_bins: option(&args, "--map-time-bins").map(|range| {
+            let bins: Vec<usize> = range
+                .split('-')
+                .map(|bin| {
+                    bin.parse()
+                        .unwrap_or_else(|_| fail("Time bins must be given as \"first-last\"!"))
+                })
+                .collect();
+            if bins.len() != 2 {
+                fail("Time bins must be given as \"first-last\"!");
+            }
+            if bins[0] > bins[1] || bins[1] >= time_bins::COUNT {
+                fail(&format!(
+                    "Time bins must satisfy first <= last < {}!",
+                    time_bins::COUNT
+                ));
+            }
+            (bins[0], bins[1])
+        }),
+    };
+    if args.get(1).map(String::as_str) == Some("analyze") {
+        analyze(
+            &dataset,
+            &read_plans(&args[2], &dataset),
+            &map_filter,
+            chain_reference.as_ref(),
+        );
+        return;
+    }
+    let run =
+        search::search(&dataset, &Config::default(), resume).unwrap_or_else(|error| fail(&error));
+    plans::write("output/plans.csv", &dataset, &run.plans);
+    println!("Wrote {} plans.", run.plans.len());
+    analyze(&dataset, &run.plans, &map_filter, chain_reference.as_ref());
+    report::write("output/report.html", &dataset, &run, &map_filter);
+}
+
+/// Plans of a file given on the command line; exits with the reason if it cannot be read.
+fn read_plans<'a>(path: &str, dataset: &'a Dataset) -> Vec<Plan<'a>> {
+    plans::read(path, dataset).unwrap_or_else(|error| fail(&error))
+}
+
+/// Writes the statistics and exports of plans.
+fn analyze(
+    dataset: &Dataset,
+    plans: &[Plan],
+    map_filter: &MapFilter,
+    chain_reference: Option<&chains::Reference>,
+) {
+    println!(
+        "{}",
+        fit::write("output/fit.csv", "output/fit_worst.csv", dataset, plans)
+    );
+    println!(
+        "{}",
+        profile::write(
+            "output/profile.csv",
+            "output/profile_fit.csv",
+            dataset,
+            plans,
+        )
+    );
+    println!(
+        "{}",
+        modal_split::write(
+            "output/mode_shares.csv",
+            "output/distances.csv",
+            dataset,
+            plans,
+        )
+    );
+    println!("{}", chains::write("output/chains.csv", plans));
+    Sankey::from_trips(dataset).write("output/sankey_trips", "Purpose flows of the input trips");
+    Sankey::from_plans(dataset, plans, false)
+        .write("output/sankey_plans", "Purpose flows of the plans");
+    Sankey::from_plans(dataset, plans, true).write(
+        "output/sankey_plans_by_mode",
+        "Purpose flows of the plans by mode",
+    );
+    map::write("output/map.svg", dataset, plans, map_filter);
+    if let Some(reference) = chain_reference {
+        println!(
+            "{}",
+            chains::compare(reference, "output/chain_deviation.csv", plans)
+        );
+    }
 }
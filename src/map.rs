@@ -0,0 +1,167 @@
+//! SVG map of the districts with the desire lines of the plans.
+
+use super::dataset::Dataset;
+use super::districts::District;
+use super::graph::{Edge, Node};
+use super::modes::Mode;
+use super::plans::Plan;
+use super::purposes::Purpose;
+use super::svg::{self, Svg};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+
+const WIDTH: f64 = 900f64;
+const HEIGHT: f64 = 900f64;
+const MARGIN: f64 = 60f64;
+const MAX_RADIUS: f64 = 18f64;
+const MAX_LINE_WIDTH: f64 = 12f64;
+const FONT_SIZE: f64 = 11f64;
+/// Above this many districts, only the most active ones are labelled.
+const LABELLED_DISTRICTS: usize = 30;
+const LINE_COLOUR: &str = "#1f3b73";
+
+/// Plan legs shown on a map; `None` shows all.
+#[derive(Clone, Copy, Default)]
+pub struct MapFilter<'a> {
+    /// Purpose of the activity a leg arrives at.
+    pub purpose: Option<Purpose>,
+    /// Mode of a leg.
+    pub mode: Option<&'a Mode>,
+    /// First and last departure time bin, inclusive.
+    pub time_bins: Option<(usize, usize)>,
+}
+impl MapFilter<'_> {
+    fn matches(&self, node: &Node, edge: &Edge) -> bool {
+        let in_time_bins = match self.time_bins {
+            Some((first, last)) => (first..=last).contains(&node.time_bin.value()),
+            None => true,
+        };
+        (self.purpose.is_none() || self.purpose == Some(edge.trip.category.destination))
+            && (self.mode.is_none() || self.mode == Some(edge.mode))
+            && in_time_bins
+    }
+    fn describe(&self) -> String {
+        let mut parts: Vec<String> = Vec::new();
+        if let Some(purpose) = self.purpose {
+            parts.push(format!("to {:?}", purpose));
+        }
+        if let Some(mode) = self.mode {
+            parts.push(format!("by {}", mode.name));
+        }
+        if let Some((first, last)) = self.time_bins {
+            parts.push(format!("departing in time bins {} to {}", first, last));
+        }
+        if parts.is_empty() {
+            "all legs".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
+/// Draws districts at their coordinates, sized by the activities legs arrive
+/// at, and desire lines between them weighted by the legs in both directions.
+pub fn to_svg(dataset: &Dataset, plans: &[Plan], filter: &MapFilter) -> Svg {
+    let districts = &dataset.districts;
+    let mut activities = vec![0usize; districts.len()];
+    let mut lines: HashMap<(usize, usize), usize> = HashMap::new();
+    for (node, edge) in plans.iter().flat_map(|plan| plan.legs.iter()) {
+        if !filter.matches(node, edge) {
+            continue;
+        }
+        let (origin, destination) = (edge.trip.origin_index, edge.trip.destination_index);
+        activities[destination] += 1;
+        if origin != destination {
+            *lines
+                .entry((origin.min(destination), origin.max(destination)))
+                .or_default() += 1;
+        }
+    }
+
+    let (min_x, max_x, min_y, max_y) = districts.iter().fold(
+        (
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+        ),
+        |(min_x, max_x, min_y, max_y), district| {
+            (
+                min_x.min(district.x),
+                max_x.max(district.x),
+                min_y.min(district.y),
+                max_y.max(district.y),
+            )
+        },
+    );
+    let extent = (max_x - min_x).max(max_y - min_y).max(f64::EPSILON);
+    let scale = (WIDTH.min(HEIGHT) - 2f64 * MARGIN) / extent;
+    // north up, centred
+    let position = |district: &District| -> (f64, f64) {
+        (
+            WIDTH / 2f64 + (district.x - (min_x + max_x) / 2f64) * scale,
+            HEIGHT / 2f64 - (district.y - (min_y + max_y) / 2f64) * scale,
+        )
+    };
+
+    let mut svg = Svg::new(WIDTH, HEIGHT);
+    let colour = filter.purpose.map_or(LINE_COLOUR, svg::purpose_colour);
+    let max_legs = lines.values().copied().max().unwrap_or(0).max(1) as f64;
+    let mut sorted_lines: Vec<(&(usize, usize), &usize)> = lines.iter().collect();
+    // thin lines first, so that thick ones stay visible
+    sorted_lines.sort_by_key(|&(&pair, &legs)| (legs, pair));
+    for (&(a, b), &legs) in sorted_lines {
+        let (first, second) = (&districts[a], &districts[b]);
+        svg.line(
+            position(first),
+            position(second),
+            colour,
+            (MAX_LINE_WIDTH * legs as f64 / max_legs).max(0.5),
+            0.5,
+            &format!("{} \u{2194} {}: {} legs", first.info, second.info, legs),
+        );
+    }
+
+    let max_activities = activities.iter().copied().max().unwrap_or(0).max(1) as f64;
+    let mut labelled: Vec<usize> = (0..districts.len()).collect();
+    labelled.sort_by_key(|&index| std::cmp::Reverse((activities[index], index)));
+    labelled.truncate(LABELLED_DISTRICTS);
+    for district in districts.iter() {
+        let count = activities[district.index];
+        let radius = (MAX_RADIUS * (count as f64 / max_activities).sqrt()).max(2f64);
+        let (x, y) = position(district);
+        let fill = if count > 0 {
+            colour
+        } else {
+            svg::NEUTRAL_COLOUR
+        };
+        svg.circle(
+            (x, y),
+            radius,
+            fill,
+            0.8,
+            &format!(
+                "{} ({}): {} activities",
+                district.info,
+                district.id.value(),
+                count
+            ),
+        );
+        if labelled.contains(&district.index) {
+            svg.text(x + radius + 3f64, y, "start", FONT_SIZE, &district.info);
+        }
+    }
+    svg.text(
+        WIDTH / 2f64,
+        MARGIN / 2f64,
+        "middle",
+        FONT_SIZE * 1.6,
+        &format!("Activities and desire lines: {}", filter.describe()),
+    );
+    svg
+}
+
+/// Writes the map of the plan legs `filter` shows as SVG.
+pub fn write<S: AsRef<OsStr>>(path: S, dataset: &Dataset, plans: &[Plan], filter: &MapFilter) {
+    to_svg(dataset, plans, filter).write(path);
+}
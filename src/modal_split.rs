@@ -0,0 +1,161 @@
+//! Realized mode shares and leg distances of plans.
+
+use super::dataset::Dataset;
+use super::districts::District;
+use super::graph::Edge;
+use super::io;
+use super::plans::Plan;
+use super::trips::{Trip, TRANSPORTS};
+use std::ffi::OsStr;
+
+/// Upper bounds of the distance bands in km; the last band is open.
+const DISTANCE_BANDS: [f64; 6] = [1f64, 2f64, 5f64, 10f64, 20f64, 50f64];
+const SHARE_HEADER: [&str; 5] = ["kind", "key", "target_share", "realized_share", "legs"];
+
+/// Beeline distance between district centres, whose coordinates are in metres.
+fn distance(origin: &District, destination: &District) -> f64 {
+    (origin.x - destination.x).hypot(origin.y - destination.y) / 1000f64
+}
+
+fn trip_distance(dataset: &Dataset, trip: &Trip) -> f64 {
+    distance(dataset.origin(trip), dataset.destination(trip))
+}
+
+fn band(distance: f64) -> usize {
+    DISTANCE_BANDS
+        .iter()
+        .position(|&upper| distance < upper)
+        .unwrap_or(DISTANCE_BANDS.len())
+}
+
+fn share(count: usize, total: usize) -> f64 {
+    if total == 0 {
+        0f64
+    } else {
+        count as f64 / total as f64
+    }
+}
+
+/// Writes realized against target shares per mode, per transport of the
+/// modes and per transport of the trips, and the distribution of leg
+/// distances per mode against that of the input trips. Returns the mode
+/// shares and mean distances per mode as two summary lines.
+pub fn write<S: AsRef<OsStr>>(
+    shares_path: S,
+    distances_path: S,
+    dataset: &Dataset,
+    plans: &[Plan],
+) -> String {
+    let modes = &dataset.modes;
+    let legs: Vec<&Edge> = plans
+        .iter()
+        .flat_map(|plan| plan.legs.iter())
+        .map(|(_, edge)| edge)
+        .collect();
+    let mut records: Vec<Vec<String>> = vec![SHARE_HEADER.iter().map(|s| s.to_string()).collect()];
+    let mut record = |kind: &str, key: &str, target: f64, count: usize| {
+        records.push(vec![
+            kind.to_string(),
+            key.to_string(),
+            format!("{:.4}", target),
+            format!("{:.4}", share(count, legs.len())),
+            count.to_string(),
+        ]);
+    };
+    let mut summary = "Mode shares (target/realized): ".to_string();
+    for mode in modes.iter() {
+        let count = legs.iter().filter(|edge| edge.mode == mode).count();
+        record("mode", mode.name, mode.share, count);
+        summary += &format!(
+            "{}: {:.3}/{:.3} | ",
+            mode.name,
+            mode.share,
+            share(count, legs.len())
+        );
+    }
+    let trip_total = dataset.trip_total();
+    for &transport in TRANSPORTS.iter() {
+        let target = modes
+            .iter()
+            .filter(|mode| mode.transport == transport)
+            .map(|mode| mode.share)
+            .sum();
+        let count = legs
+            .iter()
+            .filter(|edge| edge.mode.transport == transport)
+            .count();
+        record("mode_transport", transport.to_str(), target, count);
+    }
+    for &transport in TRANSPORTS.iter() {
+        let trip_count: usize = dataset
+            .trips
+            .iter()
+            .filter(|trip| trip.transport == transport)
+            .map(|trip| trip.count)
+            .sum();
+        let count = legs
+            .iter()
+            .filter(|edge| edge.trip.transport == transport)
+            .count();
+        record(
+            "trip_transport",
+            transport.to_str(),
+            share(trip_count, trip_total),
+            count,
+        );
+    }
+    io::write_csv(shares_path, b';', &records);
+
+    let band_count = DISTANCE_BANDS.len() + 1;
+    let mut demand = vec![0usize; band_count];
+    for trip in dataset.trips.iter() {
+        demand[band(trip_distance(dataset, trip))] += trip.count;
+    }
+    let mut by_mode = vec![vec![0usize; band_count]; modes.len()];
+    let mut distance_sums = vec![0f64; modes.len()];
+    for edge in legs.iter() {
+        let leg_distance = trip_distance(dataset, edge.trip);
+        by_mode[edge.mode.index][band(leg_distance)] += 1;
+        distance_sums[edge.mode.index] += leg_distance;
+    }
+    let mut header = vec![
+        "lower_km".to_string(),
+        "upper_km".to_string(),
+        "demand".to_string(),
+        "all".to_string(),
+    ];
+    header.extend(modes.iter().map(|mode| mode.name.to_string()));
+    let mut distance_records = vec![header];
+    for i in 0..band_count {
+        let mut distance_record = vec![
+            if i == 0 { 0f64 } else { DISTANCE_BANDS[i - 1] }.to_string(),
+            DISTANCE_BANDS
+                .get(i)
+                .map_or(String::new(), |upper| upper.to_string()),
+            format!("{:.4}", share(demand[i], trip_total)),
+            format!(
+                "{:.4}",
+                share(by_mode.iter().map(|counts| counts[i]).sum(), legs.len())
+            ),
+        ];
+        for counts in by_mode.iter() {
+            let total = counts.iter().sum();
+            distance_record.push(format!("{:.4}", share(counts[i], total)));
+        }
+        distance_records.push(distance_record);
+    }
+    io::write_csv(distances_path, b';', &distance_records);
+
+    summary += "\nMean distance in km: ";
+    for mode in modes.iter() {
+        let count: usize = by_mode[mode.index].iter().sum();
+        if count > 0 {
+            summary += &format!(
+                "{}: {:.2} | ",
+                mode.name,
+                distance_sums[mode.index] / count as f64
+            );
+        }
+    }
+    summary
+}
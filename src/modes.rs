@@ -1,13 +1,19 @@
+//! Built-in modes with their target shares of all legs.
+
 use super::trips::Transport;
-use lazy_static::lazy_static;
 use std::hash::{Hash, Hasher};
 
+/// A way of travelling a leg.
+#[non_exhaustive]
 pub struct Mode {
+    /// Index among the modes of the dataset.
     pub index: usize,
+    /// Name on the command line and in the output files.
     pub name: &'static str,
+    /// Target share of all legs.
     pub share: f64,
+    /// Kind of transport the mode belongs to.
     pub transport: Transport,
-    _priv: (),
 }
 impl PartialEq for Mode {
     fn eq(&self, other: &Self) -> bool {
@@ -21,22 +27,23 @@ impl Hash for Mode {
     }
 }
 
+/// Number of built-in modes.
 pub const COUNT: usize = 5;
 
-lazy_static! {
-    pub static ref MODES: Vec<Mode> = {
-        let mut vec: Vec<Mode> = Vec::new();
-        /*
-        * Source: "Statistisches Jahrbuch", Stadt Aachen, 2017, p.104
-        * Copyright: Stadt Aachen FB02/200
-        * License: "Nachdruck oder weitere Veröffentlichung mit Quellenangabe gestattet"
-        */
-        vec.push(Mode { index: vec.len(), name: "Feet", share: 0.298, transport: Transport::Individual, _priv: () });
-        vec.push(Mode { index: vec.len(), name: "Bike", share: 0.110, transport: Transport::Individual, _priv: () });
-        vec.push(Mode { index: vec.len(), name: "Pt", share: 0.130, transport: Transport::Public, _priv: () });
-        vec.push(Mode { index: vec.len(), name: "CarDriver", share: 0.336, transport: Transport::Individual, _priv: () });
-        vec.push(Mode { index: vec.len(), name: "CarPassenger", share: 0.126, transport: Transport::Individual, _priv: () });
-        assert!(vec.len() == COUNT);
-        vec
-    };
+/// Modes with their target shares of all legs.
+#[allow(clippy::vec_init_then_push)]
+pub(crate) fn load() -> Vec<Mode> {
+    let mut vec: Vec<Mode> = Vec::new();
+    /*
+    * Source: "Statistisches Jahrbuch", Stadt Aachen, 2017, p.104
+    * Copyright: Stadt Aachen FB02/200
+    * License: "Nachdruck oder weitere Veröffentlichung mit Quellenangabe gestattet"
+    */
+    vec.push(Mode { index: vec.len(), name: "Feet", share: 0.298, transport: Transport::Individual });
+    vec.push(Mode { index: vec.len(), name: "Bike", share: 0.110, transport: Transport::Individual });
+    vec.push(Mode { index: vec.len(), name: "Pt", share: 0.130, transport: Transport::Public });
+    vec.push(Mode { index: vec.len(), name: "CarDriver", share: 0.336, transport: Transport::Individual });
+    vec.push(Mode { index: vec.len(), name: "CarPassenger", share: 0.126, transport: Transport::Individual });
+    assert!(vec.len() == COUNT);
+    vec
 }
@@ -0,0 +1,221 @@
+//! Plans, the chains of legs found by the search, and their plan files.
+
+use super::dataset::Dataset;
+use super::graph::{Edge, Node};
+use super::io;
+use super::time_bins::TIME_BINS;
+use super::trips::{Scaling, Transport, Trip};
+use csv::StringRecord;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::Path;
+use std::str::FromStr;
+
+const HEADER: [&str; 8] = [
+    "plan",
+    "stage",
+    "time_bin",
+    "transport",
+    "category",
+    "origin",
+    "destination",
+    "mode",
+];
+
+/// Version of the plan records, raised whenever their columns change; version 2
+/// added the stage column, version 3 the scaling record before the header.
+pub const FORMAT_VERSION: usize = 3;
+
+/// Stage of plans changed by the improvement after the search; stages count from 1.
+pub const IMPROVED: usize = 0;
+
+/// A day of activities connected by legs.
+#[derive(Clone)]
+pub struct Plan<'a> {
+    /// Stage that found the plan, or `IMPROVED`.
+    pub stage: usize,
+    /// Legs, each with the node it departs from.
+    pub legs: Vec<(Node, Edge<'a>)>,
+}
+
+/// Stage of plans as written to the output files.
+pub fn stage_name(stage: usize) -> String {
+    if stage == IMPROVED {
+        "improved".to_string()
+    } else {
+        stage.to_string()
+    }
+}
+
+/// Writes plans as records of their legs, after the scaling of the trips of
+/// `dataset` they use.
+pub fn write<S: AsRef<OsStr>>(path: S, dataset: &Dataset, plans: &[Plan]) {
+    let mut records = vec![
+        vec![
+            "scaling".to_string(),
+            dataset.scaling.factor.to_string(),
+            dataset.scaling.integerization.to_string(),
+        ],
+        HEADER.iter().map(|s| s.to_string()).collect(),
+    ];
+    records.append(&mut to_records(dataset, plans));
+    io::write_csv(path, b';', &records);
+}
+
+/// Plans of a file written by `write`, resolved against the trips and modes of
+/// `dataset`, whose trips must be scaled like those the plans were made of.
+pub fn read<S: AsRef<OsStr>>(path: S, dataset: &Dataset) -> Result<Vec<Plan<'_>>, String> {
+    let path = Path::new(&path);
+    let records = io::try_read_csv(path, false, false, b';', None)?;
+    check_scaling(records.first(), &dataset.scaling)
+        .and_then(|()| check_header(records.get(1)))
+        .and_then(|()| from_records(dataset, &records[2..]))
+        .map_err(|error| format!("{}: {}", path.display(), error))
+}
+
+/// Fails for files without a scaling record and those of plans made of trips
+/// scaled otherwise than by `scaling`.
+fn check_scaling(record: Option<&StringRecord>, scaling: &Scaling) -> Result<(), String> {
+    let record = match record {
+        Some(record) if record.get(0) == Some("scaling") => record,
+        _ => {
+            return Err(format!(
+                "Missing scaling record of plan format version {}; plans written by older versions have to be searched again!",
+                FORMAT_VERSION
+            ))
+        }
+    };
+    let found = Scaling {
+        factor: io::parse_field(record, 1, "trip factor")?,
+        integerization: io::parse_field(record, 2, "integerization")?,
+    };
+    if found == *scaling {
+        Ok(())
+    } else {
+        Err(format!(
+            "Plans were made of trips scaled by factor {} with {} integerization, not {} with {}; pass the same --trip-factor and --integerization!",
+            found.factor, found.integerization, scaling.factor, scaling.integerization
+        ))
+    }
+}
+
+/// Fails for files without the header of the current format version.
+fn check_header(header: Option<&StringRecord>) -> Result<(), String> {
+    let found: Vec<&str> = header.map_or(Vec::new(), |header| header.iter().collect());
+    if found == HEADER {
+        Ok(())
+    } else {
+        Err(format!(
+            "Unsupported header \"{}\", expected \"{}\" of plan format version {}; plans written by older versions have to be searched again!",
+            found.join(";"),
+            HEADER.join(";"),
+            FORMAT_VERSION
+        ))
+    }
+}
+
+/// One record per leg, identifying the trip by transport, category and districts.
+pub fn to_records(dataset: &Dataset, plans: &[Plan]) -> Vec<Vec<String>> {
+    let mut records: Vec<Vec<String>> = Vec::new();
+    for (plan_index, plan) in plans.iter().enumerate() {
+        for (node, edge) in plan.legs.iter() {
+            records.push(vec![
+                plan_index.to_string(),
+                plan.stage.to_string(),
+                node.time_bin.value().to_string(),
+                edge.trip.transport.to_str().to_string(),
+                edge.trip.category.id.value().to_string(),
+                dataset.origin(edge.trip).id.value().to_string(),
+                dataset.destination(edge.trip).id.value().to_string(),
+                edge.mode.name.to_string(),
+            ]);
+        }
+    }
+    records
+}
+
+/// Rebuilds plans from records of `to_records`. Each leg must depart from
+/// the node the previous leg of its plan arrives at.
+pub fn from_records<'a>(
+    dataset: &'a Dataset,
+    records: &[StringRecord],
+) -> Result<Vec<Plan<'a>>, String> {
+    let trips: HashMap<(Transport, u8, u16, u16), &Trip> = dataset
+        .trips
+        .iter()
+        .map(|trip| {
+            let key = (
+                trip.transport,
+                trip.category.id.value(),
+                dataset.origin(trip).id.value(),
+                dataset.destination(trip).id.value(),
+            );
+            (key, trip)
+        })
+        .collect();
+    let mut plans: Vec<Plan> = Vec::new();
+    for (index, record) in records.iter().enumerate() {
+        let leg = |error: String| format!("leg {}: {}", index + 1, error);
+        if record.len() != HEADER.len() {
+            return Err(leg(format!(
+                "Expected {} fields, found {}!",
+                HEADER.len(),
+                record.len()
+            )));
+        }
+        fn parse<T: FromStr>(record: &StringRecord, column: usize) -> Result<T, String> {
+            record[column]
+                .parse()
+                .map_err(|_| format!("Invalid {} \"{}\"!", HEADER[column], &record[column]))
+        }
+        let field = |column: usize| parse::<usize>(record, column).map_err(leg);
+        let plan_index = field(0)?;
+        let stage = field(1)?;
+        if plan_index == plans.len() {
+            plans.push(Plan {
+                stage,
+                legs: Vec::new(),
+            });
+        } else if plan_index + 1 != plans.len() {
+            return Err(leg(format!("Plan {} out of order!", plan_index)));
+        } else if plans[plan_index].stage != stage {
+            return Err(leg(format!("Plan {} changes its stage!", plan_index)));
+        }
+        let time_bin = *TIME_BINS
+            .get(field(2)?)
+            .ok_or_else(|| leg(format!("Unknown time bin \"{}\"!", &record[2])))?;
+        let key = (
+            Transport::from_str(&record[3]).map_err(leg)?,
+            parse::<u8>(record, 4).map_err(leg)?,
+            parse::<u16>(record, 5).map_err(leg)?,
+            parse::<u16>(record, 6).map_err(leg)?,
+        );
+        let trip = *trips.get(&key).ok_or_else(|| {
+            leg(format!(
+                "Unknown trip {} {} from {} to {}!",
+                &record[3], key.1, key.2, key.3
+            ))
+        })?;
+        let mode = dataset
+            .modes
+            .iter()
+            .find(|mode| mode.name == &record[7])
+            .ok_or_else(|| leg(format!("Unknown mode \"{}\"!", &record[7])))?;
+        let node = Node {
+            district_index: trip.origin_index,
+            purpose: trip.category.origin,
+            time_bin,
+        };
+        let legs = &mut plans.last_mut().unwrap().legs;
+        if let Some((previous_node, previous_edge)) = legs.last() {
+            if previous_edge.target(previous_node) != node {
+                return Err(leg(format!(
+                    "Plan {} does not continue where its previous leg arrives!",
+                    plan_index
+                )));
+            }
+        }
+        legs.push((node, Edge { trip, mode }));
+    }
+    Ok(plans)
+}
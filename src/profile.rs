@@ -0,0 +1,87 @@
+//! Departure time profile of plans against the pegel.
+
+use super::dataset::Dataset;
+use super::io;
+use super::plans::Plan;
+use super::time_bins;
+use std::ffi::OsStr;
+
+const HEADER: [&str; 5] = [
+    "category",
+    "time_bin",
+    "target_share",
+    "realized_share",
+    "departures",
+];
+const SUMMARY_HEADER: [&str; 4] = ["category", "departures", "total_variation", "max_deviation"];
+
+/// Compares the departures of plan legs over the time bins with the pegel
+/// shares per category. Writes one row per category and time bin, and a
+/// summary with the total variation distance and the largest deviation of a
+/// single bin per category. Returns the total variation per category as a
+/// summary line.
+pub fn write<S: AsRef<OsStr>>(
+    path: S,
+    summary_path: S,
+    dataset: &Dataset,
+    plans: &[Plan],
+) -> String {
+    let mut departures = vec![[0usize; time_bins::COUNT]; dataset.categories.len()];
+    for (node, edge) in plans.iter().flat_map(|plan| plan.legs.iter()) {
+        departures[edge.trip.category.index][node.time_bin.value()] += 1;
+    }
+
+    let mut records: Vec<Vec<String>> = vec![HEADER.iter().map(|s| s.to_string()).collect()];
+    let mut summary_records: Vec<Vec<String>> =
+        vec![SUMMARY_HEADER.iter().map(|s| s.to_string()).collect()];
+    let mut summary = "Time profile deviation: ".to_string();
+    for category in dataset.categories.iter() {
+        let levels = dataset.levels_of(category);
+        let level_sum: f64 = levels.iter().sum();
+        let counts = &departures[category.index];
+        let count_sum: usize = counts.iter().sum();
+        let mut total_variation = 0f64;
+        let mut max_deviation = 0f64;
+        for bin in 0..time_bins::COUNT {
+            let target = if level_sum > 0f64 {
+                levels[bin] / level_sum
+            } else {
+                0f64
+            };
+            let realized = if count_sum > 0 {
+                counts[bin] as f64 / count_sum as f64
+            } else {
+                0f64
+            };
+            total_variation += (realized - target).abs() / 2f64;
+            max_deviation = max_deviation.max((realized - target).abs());
+            records.push(vec![
+                category.id.value().to_string(),
+                bin.to_string(),
+                format!("{:.4}", target),
+                format!("{:.4}", realized),
+                counts[bin].to_string(),
+            ]);
+        }
+        // without departures there is no realized distribution to compare
+        if count_sum == 0 {
+            summary_records.push(vec![
+                category.id.value().to_string(),
+                count_sum.to_string(),
+                String::new(),
+                String::new(),
+            ]);
+            continue;
+        }
+        summary_records.push(vec![
+            category.id.value().to_string(),
+            count_sum.to_string(),
+            format!("{:.4}", total_variation),
+            format!("{:.4}", max_deviation),
+        ]);
+        summary += &format!("{}: {:.3} | ", category.id.value(), total_variation);
+    }
+    io::write_csv(path, b';', &records);
+    io::write_csv(summary_path, b';', &summary_records);
+    summary
+}
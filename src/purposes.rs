@@ -1,15 +1,35 @@
+//! Purposes of activities and how long they last.
+
 use std::str::FromStr;
 use std::time::Duration;
 
+/// Purpose of an activity, as grouped from the purposes of the input files.
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Purpose {
+    /// "Wohnen".
     Home,
+    /// "Freizeit".
     Leisure,
+    /// "Arbeit".
     Work,
+    /// Schools and lecture halls.
     School,
+    /// "Dienstleistung" and "Service".
     Service,
+    /// "Einkaufen".
     Shopping,
 }
+/// Number of purposes.
+pub const COUNT: usize = 6;
+/// All purposes, in the order of their indices.
+pub const PURPOSES: [Purpose; COUNT] = [
+    Purpose::Home,
+    Purpose::Leisure,
+    Purpose::Work,
+    Purpose::School,
+    Purpose::Service,
+    Purpose::Shopping,
+];
 impl FromStr for Purpose {
     type Err = String;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -32,6 +52,22 @@ impl FromStr for Purpose {
     }
 }
 impl Purpose {
+    /// Position among `PURPOSES`.
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+    /// Letter of the purpose in activity chain patterns.
+    pub fn code(&self) -> char {
+        match self {
+            Self::Home => 'H',
+            Self::Leisure => 'L',
+            Self::Work => 'W',
+            Self::School => 'S',
+            Self::Service => 'V',
+            Self::Shopping => 'P',
+        }
+    }
+    /// How long an activity of the purpose lasts.
     pub fn duration(&self) -> Duration {
         macro_rules! hours {
             ($h:expr) => {
@@ -0,0 +1,119 @@
+//! Bounds on how far the nearest activity of a purpose is, to prune
+//! branches that cannot return in time.
+
+use super::graph::{self, Graph, Node};
+use super::purposes::{self, Purpose, PURPOSES};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+
+const UNREACHABLE: u8 = u8::MAX;
+
+#[derive(Clone, Copy)]
+struct Bounds {
+    legs: [u8; purposes::COUNT],
+    bins: [u8; purposes::COUNT],
+}
+
+/// Lower bounds on the number of legs and time bins needed to get from a node
+/// to any node of a given purpose. Computed once on the full graph, so they
+/// stay valid while capacities shrink. Every trip leaves at every time bin, so
+/// the bounds only depend on the district and purpose of a node.
+pub struct Reachability(Vec<Option<Bounds>>);
+impl Reachability {
+    /// Number of districts and purposes with trips.
+    pub(crate) fn key_count(&self) -> usize {
+        self.0.iter().filter(|bounds| bounds.is_some()).count()
+    }
+    /// Bounds of every used key of `graph`.
+    pub fn new(graph: &Graph) -> Self {
+        let key_count = graph.key_count();
+        // reversed edges between keys: (source, bins)
+        let mut incoming: Vec<Vec<(usize, u8)>> = vec![Vec::new(); key_count];
+        let mut is_used = vec![false; key_count];
+        for (source, targets) in graph.key_edges().into_iter().enumerate() {
+            for (target, bins) in targets {
+                incoming[target].push((source, bins));
+                is_used[source] = true;
+                is_used[target] = true;
+            }
+        }
+        let keys: Vec<usize> = (0..key_count).filter(|&key| is_used[key]).collect();
+
+        let mut bounds = vec![
+            Bounds {
+                legs: [UNREACHABLE; purposes::COUNT],
+                bins: [UNREACHABLE; purposes::COUNT],
+            };
+            key_count
+        ];
+        for purpose in PURPOSES.iter() {
+            let p = purpose.index();
+            let goals: Vec<usize> = keys
+                .iter()
+                .copied()
+                .filter(|&key| key % purposes::COUNT == p)
+                .collect();
+
+            // fewest legs: breadth-first search on the reversed graph
+            let mut queue: VecDeque<usize> = VecDeque::new();
+            for &goal in goals.iter() {
+                bounds[goal].legs[p] = 0;
+                queue.push_back(goal);
+            }
+            while let Some(target) = queue.pop_front() {
+                let legs = bounds[target].legs[p].saturating_add(1);
+                for &(source, _) in incoming[target].iter() {
+                    if legs < bounds[source].legs[p] {
+                        bounds[source].legs[p] = legs;
+                        queue.push_back(source);
+                    }
+                }
+            }
+
+            // fewest time bins: Dijkstra on the reversed graph
+            let mut heap: BinaryHeap<Reverse<(u8, usize)>> = BinaryHeap::new();
+            for &goal in goals.iter() {
+                bounds[goal].bins[p] = 0;
+                heap.push(Reverse((0, goal)));
+            }
+            while let Some(Reverse((bins, target))) = heap.pop() {
+                if bins > bounds[target].bins[p] {
+                    continue;
+                }
+                for &(source, edge_bins) in incoming[target].iter() {
+                    let source_bins = bins.saturating_add(edge_bins);
+                    if source_bins < bounds[source].bins[p] {
+                        bounds[source].bins[p] = source_bins;
+                        heap.push(Reverse((source_bins, source)));
+                    }
+                }
+            }
+        }
+        Reachability(
+            bounds
+                .into_iter()
+                .zip(is_used)
+                .map(|(bounds, is_used)| if is_used { Some(bounds) } else { None })
+                .collect(),
+        )
+    }
+    fn bounds(&self, node: &Node) -> Option<&Bounds> {
+        self.0
+            .get(graph::key_index(node.district_index, node.purpose))
+            .and_then(Option::as_ref)
+    }
+    /// Minimum number of legs from `node` to a node with `purpose`, `usize::MAX` if unreachable.
+    pub fn legs(&self, node: &Node, purpose: Purpose) -> usize {
+        Self::value(self.bounds(node).map(|bounds| bounds.legs[purpose.index()]))
+    }
+    /// Minimum number of time bins from `node` to a node with `purpose`, `usize::MAX` if unreachable.
+    pub fn bins(&self, node: &Node, purpose: Purpose) -> usize {
+        Self::value(self.bounds(node).map(|bounds| bounds.bins[purpose.index()]))
+    }
+    fn value(bound: Option<u8>) -> usize {
+        match bound {
+            Some(value) if value != UNREACHABLE => value as usize,
+            _ => usize::MAX,
+        }
+    }
+}
@@ -0,0 +1,290 @@
+//! Self-contained HTML report of a search run.
+
+use super::chains;
+use super::dataset::Dataset;
+use super::io;
+use super::map::{self, MapFilter};
+use super::plans::{self, Plan};
+use super::residuals;
+use super::sankey::Sankey;
+use super::search::Run;
+use super::svg::{self, Svg};
+use itertools::Itertools;
+use std::ffi::OsStr;
+use std::fmt::Write;
+use std::time::Duration;
+
+const REPORTED_PATTERNS: usize = 20;
+const CHART_WIDTH: f64 = 640f64;
+const CHART_HEIGHT: f64 = 280f64;
+const CHART_MARGIN: f64 = 36f64;
+const BAR_COLOUR: &str = "#4e79a7";
+const FONT_SIZE: f64 = 12f64;
+const STYLE: &str = "body{font-family:sans-serif;margin:2em auto;max-width:1000px;color:#222}\
+table{border-collapse:collapse;margin:0.5em 0 1.5em}\
+th,td{border:1px solid #ccc;padding:0.25em 0.6em;text-align:right;vertical-align:top}\
+th:first-child,td:first-child{text-align:left}\
+pre{margin:0;text-align:left}\
+svg{display:block;margin:0.5em 0 1.5em;max-width:100%;height:auto}";
+
+fn hms(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!(
+        "{:02}:{:02}:{:02}",
+        secs / 3600,
+        (secs / 60) % 60,
+        secs % 60
+    )
+}
+
+fn percent(part: usize, total: usize) -> String {
+    if total == 0 {
+        String::new()
+    } else {
+        format!("{:.1}%", 100f64 * part as f64 / total as f64)
+    }
+}
+
+/// Table of escaped cells, or of preformatted ones where `pre` is set.
+fn table(header: &[&str], rows: &[Vec<String>], pre: bool) -> String {
+    let mut html = String::from("<table>\n<tr>");
+    for cell in header.iter() {
+        write!(html, "<th>{}</th>", svg::escape(cell)).unwrap();
+    }
+    html.push_str("</tr>\n");
+    for row in rows.iter() {
+        html.push_str("<tr>");
+        for (index, cell) in row.iter().enumerate() {
+            if pre && index > 0 {
+                write!(html, "<td><pre>{}</pre></td>", svg::escape(cell)).unwrap();
+            } else {
+                write!(html, "<td>{}</td>", svg::escape(cell)).unwrap();
+            }
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</table>\n");
+    html
+}
+
+/// Vertical bars with their values above and labels below.
+fn bar_chart(title: &str, bars: &[(String, usize)]) -> Svg {
+    let mut svg = Svg::new(CHART_WIDTH, CHART_HEIGHT);
+    svg.text(
+        CHART_WIDTH / 2f64,
+        CHART_MARGIN / 2f64,
+        "middle",
+        FONT_SIZE * 1.3,
+        title,
+    );
+    let max = bars
+        .iter()
+        .map(|(_, value)| *value)
+        .max()
+        .unwrap_or(0)
+        .max(1) as f64;
+    let inner_height = CHART_HEIGHT - 3f64 * CHART_MARGIN;
+    let step = (CHART_WIDTH - 2f64 * CHART_MARGIN) / bars.len().max(1) as f64;
+    let bottom = CHART_HEIGHT - CHART_MARGIN;
+    for (index, (label, value)) in bars.iter().enumerate() {
+        let height = inner_height * *value as f64 / max;
+        let x = CHART_MARGIN + index as f64 * step;
+        svg.rect(
+            x + step * 0.1,
+            bottom - height,
+            step * 0.8,
+            height,
+            BAR_COLOUR,
+            1f64,
+        );
+        let centre = x + step / 2f64;
+        svg.text(
+            centre,
+            bottom - height - FONT_SIZE,
+            "middle",
+            FONT_SIZE,
+            &value.to_string(),
+        );
+        svg.text(centre, bottom + FONT_SIZE, "middle", FONT_SIZE, label);
+    }
+    svg
+}
+
+/// Writes a single HTML file with the configuration, the stages, plan
+/// lengths, capacity coverage, chain patterns and charts of a run.
+pub fn write<S: AsRef<OsStr>>(path: S, dataset: &Dataset, run: &Run, map_filter: &MapFilter) {
+    let plans: &[Plan] = &run.plans;
+    let mut html = String::new();
+    writeln!(
+        html,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Day plans run report</title>\n<style>{}</style>\n</head>\n<body>",
+        STYLE
+    )
+    .unwrap();
+    html.push_str("<h1>Day plans run report</h1>\n");
+    writeln!(
+        html,
+        "<p>{} plans using {} trips, found in {}{}.</p>",
+        plans.len(),
+        plans.iter().map(|plan| plan.legs.len()).sum::<usize>(),
+        hms(run.elapsed),
+        if run.resumed {
+            ", resumed from a checkpoint"
+        } else {
+            ""
+        }
+    )
+    .unwrap();
+
+    html.push_str("<h2>Configuration</h2>\n");
+    let configuration: Vec<Vec<String>> = run
+        .config
+        .describe()
+        .into_iter()
+        .map(|(name, value)| vec![name, value])
+        .collect();
+    html.push_str(&table(&["setting", "value"], &configuration, true));
+
+    html.push_str("<h2>Stages</h2>\n");
+    let stage_rows: Vec<Vec<String>> = run
+        .stages
+        .iter()
+        .map(|stage| {
+            vec![
+                stage.name.clone(),
+                stage.chunks.map_or(String::new(), |(done, total)| {
+                    format!("{} of {}", done, total)
+                }),
+                format!("{:.2e}", stage.steps),
+                hms(stage.elapsed),
+                match stage.stop_reason {
+                    Some(reason) => reason.to_string(),
+                    None => "completed".to_string(),
+                },
+                stage.plans.to_string(),
+                stage.trips_used.to_string(),
+            ]
+        })
+        .collect();
+    html.push_str(&table(
+        &[
+            "stage",
+            "chunks",
+            "steps",
+            "time",
+            "end",
+            "plans after",
+            "trips used after",
+        ],
+        &stage_rows,
+        false,
+    ));
+
+    html.push_str("<h2>Plan lengths</h2>\n");
+    let max_length = plans.iter().map(|plan| plan.legs.len()).max().unwrap_or(0);
+    let lengths: Vec<usize> = (1..=max_length).collect();
+    let stages: Vec<usize> = plans
+        .iter()
+        .map(|plan| plan.stage)
+        .unique()
+        .sorted_by_key(|&stage| (stage == plans::IMPROVED, stage))
+        .collect();
+    let mut length_rows: Vec<Vec<String>> = Vec::new();
+    for stage in stages.into_iter().map(Some).chain(std::iter::once(None)) {
+        let stage_plans: Vec<&Plan> = plans
+            .iter()
+            .filter(|plan| stage.is_none() || stage == Some(plan.stage))
+            .collect();
+        let mut row = vec![stage.map_or("all".to_string(), plans::stage_name)];
+        row.extend(lengths.iter().map(|&length| {
+            stage_plans
+                .iter()
+                .filter(|plan| plan.legs.len() == length)
+                .count()
+                .to_string()
+        }));
+        row.push(stage_plans.len().to_string());
+        length_rows.push(row);
+    }
+    let length_header: Vec<String> = lengths.iter().map(|length| length.to_string()).collect();
+    let mut header: Vec<&str> = vec!["stage"];
+    header.extend(length_header.iter().map(|length| length.as_str()));
+    header.push("total");
+    html.push_str(&table(&header, &length_rows, false));
+    let bars: Vec<(String, usize)> = lengths
+        .iter()
+        .map(|&length| {
+            let count = plans
+                .iter()
+                .filter(|plan| plan.legs.len() == length)
+                .count();
+            (format!("{} legs", length), count)
+        })
+        .collect();
+    html.push_str(&bar_chart("Plans by number of legs", &bars).to_string());
+
+    html.push_str("<h2>Coverage</h2>\n");
+    let residuals = residuals::residuals(dataset, &run.initial_capacities, &run.capacities);
+    let used_row = |name: String, unused: usize, total: usize| {
+        let used = total.saturating_sub(unused);
+        vec![
+            name,
+            used.to_string(),
+            total.to_string(),
+            percent(used, total),
+        ]
+    };
+    let mut coverage_rows: Vec<Vec<String>> = residuals::totals(&residuals)
+        .iter()
+        .map(|&(name, unused, total)| used_row(name.to_string(), unused, total))
+        .collect();
+    for residual in residuals.iter() {
+        let name = match residual.capacity {
+            "trips_by_category" => format!("trips of category {}", residual.key),
+            "mode" => format!("mode {}", residual.key),
+            _ => continue,
+        };
+        coverage_rows.push(used_row(name, residual.unused, residual.initial));
+    }
+    html.push_str(&table(
+        &["capacity", "used", "initial", "share"],
+        &coverage_rows,
+        false,
+    ));
+    html.push_str("<h2>Chain patterns</h2>\n");
+    let frequencies = chains::frequencies(plans.iter());
+    let pattern_rows: Vec<Vec<String>> = frequencies
+        .iter()
+        .take(REPORTED_PATTERNS)
+        .map(|(pattern, count)| {
+            vec![
+                pattern.to_string(),
+                count.to_string(),
+                percent(*count, plans.len()),
+            ]
+        })
+        .collect();
+    html.push_str(&table(&["pattern", "plans", "share"], &pattern_rows, false));
+    writeln!(
+        html,
+        "<p>{} distinct patterns; the {} most frequent are shown.</p>",
+        frequencies.len(),
+        REPORTED_PATTERNS.min(frequencies.len())
+    )
+    .unwrap();
+
+    html.push_str("<h2>Charts</h2>\n");
+    html.push_str(
+        &Sankey::from_trips(dataset)
+            .to_svg("Purpose flows of the input trips")
+            .to_string(),
+    );
+    html.push_str(
+        &Sankey::from_plans(dataset, plans, true)
+            .to_svg("Purpose flows of the plans by mode")
+            .to_string(),
+    );
+    html.push_str(&map::to_svg(dataset, plans, map_filter).to_string());
+    html.push_str("</body>\n</html>\n");
+    io::write_text(path, &html);
+}
@@ -0,0 +1,126 @@
+use super::capacities::Capacities;
+use super::dataset::Dataset;
+use super::io;
+use super::time_bins::{TimeBin, TIME_BINS};
+use std::ffi::OsStr;
+
+const HEADER: [&str; 5] = ["capacity", "key", "time_bin", "unused", "initial"];
+
+/// What is left of one capacity or of a sum of trip capacities.
+pub struct Residual {
+    /// "trips_by_category", "trips_by_origin", "trips_by_destination", "level" or "mode".
+    pub capacity: &'static str,
+    /// Category id, district id or mode name.
+    pub key: String,
+    pub time_bin: Option<TimeBin>,
+    pub unused: usize,
+    pub initial: usize,
+}
+impl Residual {
+    fn to_record(&self) -> Vec<String> {
+        vec![
+            self.capacity.to_string(),
+            self.key.clone(),
+            self.time_bin
+                .map_or(String::new(), |time_bin| time_bin.value().to_string()),
+            self.unused.to_string(),
+            self.initial.to_string(),
+        ]
+    }
+}
+
+/// What is left of the capacities: trips by category, origin and destination
+/// district, levels by category and time bin, and modes.
+pub fn residuals(dataset: &Dataset, initial: &Capacities, remaining: &Capacities) -> Vec<Residual> {
+    let mut residuals: Vec<Residual> = Vec::new();
+    let residual =
+        |capacity: &'static str, key: String, time_bin: Option<TimeBin>, unused, initial| {
+            Residual {
+                capacity,
+                key,
+                time_bin,
+                unused,
+                initial,
+            }
+        };
+
+    let mut by_category = vec![(0, 0); dataset.categories.len()];
+    let mut by_origin = vec![(0, 0); dataset.districts.len()];
+    let mut by_destination = vec![(0, 0); dataset.districts.len()];
+    for trip in dataset.trips.iter() {
+        let unused = remaining.get_trip(trip);
+        for (unused_sum, total) in [
+            &mut by_category[trip.category.index],
+            &mut by_origin[trip.origin_index],
+            &mut by_destination[trip.destination_index],
+        ] {
+            *unused_sum += unused;
+            *total += trip.count;
+        }
+    }
+    for category in dataset.categories.iter() {
+        let (unused, total) = by_category[category.index];
+        let key = category.id.value().to_string();
+        residuals.push(residual("trips_by_category", key, None, unused, total));
+    }
+    for (capacity, sums) in [
+        ("trips_by_origin", &by_origin),
+        ("trips_by_destination", &by_destination),
+    ] {
+        for district in dataset.districts.iter() {
+            let (unused, total) = sums[district.index];
+            if total > 0 {
+                let key = district.id.value().to_string();
+                residuals.push(residual(capacity, key, None, unused, total));
+            }
+        }
+    }
+    for category in dataset.categories.iter() {
+        for &time_bin in TIME_BINS.iter() {
+            let total = initial.unused_level(category, time_bin);
+            if total > 0 {
+                residuals.push(residual(
+                    "level",
+                    category.id.value().to_string(),
+                    Some(time_bin),
+                    remaining.unused_level(category, time_bin),
+                    total,
+                ));
+            }
+        }
+    }
+    for mode in dataset.modes.iter() {
+        residuals.push(residual(
+            "mode",
+            mode.name.to_string(),
+            None,
+            remaining.unused_mode(mode),
+            initial.unused_mode(mode),
+        ));
+    }
+    residuals
+}
+
+/// Unused and initial totals of trips, level capacity and mode budget.
+pub fn totals(residuals: &[Residual]) -> [(&'static str, usize, usize); 3] {
+    let sum = |capacity: &str| -> (usize, usize) {
+        residuals
+            .iter()
+            .filter(|residual| residual.capacity == capacity)
+            .fold((0, 0), |(unused, initial), residual| {
+                (unused + residual.unused, initial + residual.initial)
+            })
+    };
+    let (trips, levels, modes) = (sum("trips_by_category"), sum("level"), sum("mode"));
+    [
+        ("trips", trips.0, trips.1),
+        ("level capacity", levels.0, levels.1),
+        ("mode budget", modes.0, modes.1),
+    ]
+}
+
+pub fn write<S: AsRef<OsStr>>(path: S, residuals: &[Residual]) {
+    let mut records: Vec<Vec<String>> = vec![HEADER.iter().map(|s| s.to_string()).collect()];
+    records.extend(residuals.iter().map(Residual::to_record));
+    io::write_csv(path, b';', &records);
+}
@@ -0,0 +1,239 @@
+//! Controlled rounding of a matrix: every value is rounded down or up such
+//! that every row and column sum and the total are rounded down or up as well.
+//!
+//! The fractional parts of the values are a flow from the rows through their
+//! values to the columns. Bounding each row, column and the total by the
+//! floor and ceiling of its fractional sum, an integral flow within those
+//! bounds exists and selects the values to round up.
+
+use std::collections::VecDeque;
+
+/// Edges of a flow network with their remaining capacities; the reverse of
+/// edge `e` is `e ^ 1`.
+struct Network {
+    targets: Vec<usize>,
+    capacities: Vec<i64>,
+    outgoing: Vec<Vec<usize>>,
+}
+impl Network {
+    fn new(nodes: usize) -> Self {
+        Network {
+            targets: Vec::new(),
+            capacities: Vec::new(),
+            outgoing: vec![Vec::new(); nodes],
+        }
+    }
+    fn add_edge(&mut self, source: usize, target: usize, capacity: i64) -> usize {
+        let edge = self.targets.len();
+        self.targets.extend([target, source]);
+        self.capacities.extend([capacity, 0]);
+        self.outgoing[source].push(edge);
+        self.outgoing[target].push(edge + 1);
+        edge
+    }
+    /// Flow through an edge added with `capacity`.
+    fn flow(&self, edge: usize) -> i64 {
+        self.capacities[edge ^ 1]
+    }
+    /// Maximum flow from `source` to `sink` by Dinic's algorithm.
+    fn max_flow(&mut self, source: usize, sink: usize) -> i64 {
+        let mut total = 0;
+        loop {
+            let mut levels = vec![usize::MAX; self.outgoing.len()];
+            levels[source] = 0;
+            let mut queue = VecDeque::from(vec![source]);
+            while let Some(node) = queue.pop_front() {
+                for &edge in self.outgoing[node].iter() {
+                    let target = self.targets[edge];
+                    if self.capacities[edge] > 0 && levels[target] == usize::MAX {
+                        levels[target] = levels[node] + 1;
+                        queue.push_back(target);
+                    }
+                }
+            }
+            if levels[sink] == usize::MAX {
+                return total;
+            }
+            let mut next = vec![0; self.outgoing.len()];
+            loop {
+                let pushed = self.augment(source, sink, i64::MAX, &levels, &mut next);
+                if pushed == 0 {
+                    break;
+                }
+                total += pushed;
+            }
+        }
+    }
+    /// Pushes up to `limit` along a path of increasing levels; iterative, as
+    /// paths may be as long as the network.
+    fn augment(
+        &mut self,
+        source: usize,
+        sink: usize,
+        limit: i64,
+        levels: &[usize],
+        next: &mut [usize],
+    ) -> i64 {
+        let mut path: Vec<usize> = Vec::new();
+        let mut node = source;
+        loop {
+            if node == sink {
+                let pushed = path
+                    .iter()
+                    .map(|&edge| self.capacities[edge])
+                    .fold(limit, i64::min);
+                for &edge in path.iter() {
+                    self.capacities[edge] -= pushed;
+                    self.capacities[edge ^ 1] += pushed;
+                }
+                return pushed;
+            }
+            let mut advanced = false;
+            while next[node] < self.outgoing[node].len() {
+                let edge = self.outgoing[node][next[node]];
+                let target = self.targets[edge];
+                if self.capacities[edge] > 0 && levels[target] == levels[node] + 1 {
+                    path.push(edge);
+                    node = target;
+                    advanced = true;
+                    break;
+                }
+                next[node] += 1;
+            }
+            if !advanced {
+                // a dead end: retreat and skip the edge leading here
+                match path.pop() {
+                    Some(edge) => {
+                        node = self.targets[edge ^ 1];
+                        next[node] += 1;
+                    }
+                    None => return 0,
+                }
+            }
+        }
+    }
+}
+
+/// Non-negative `values` at (row, column), rounded down or up so that the
+/// sum of every row, every column and of all values stays within one of its
+/// unrounded sum.
+pub(crate) fn round(values: &[(usize, usize, f64)]) -> Vec<usize> {
+    let rows = values.iter().map(|&(row, _, _)| row + 1).max().unwrap_or(0);
+    let columns = values
+        .iter()
+        .map(|&(_, column, _)| column + 1)
+        .max()
+        .unwrap_or(0);
+    let fractions: Vec<f64> = values
+        .iter()
+        .map(|&(_, _, value)| value - value.floor())
+        .collect();
+    let mut row_sums = vec![0f64; rows];
+    let mut column_sums = vec![0f64; columns];
+    for (&(row, column, _), fraction) in values.iter().zip(fractions.iter()) {
+        row_sums[row] += fraction;
+        column_sums[column] += fraction;
+    }
+    let total: f64 = fractions.iter().sum();
+
+    // rows, columns, source, sink and the two nodes for lower bounds
+    let (source, sink) = (rows + columns, rows + columns + 1);
+    let (lower_source, lower_sink) = (sink + 1, sink + 2);
+    let mut network = Network::new(sink + 3);
+    let mut excess = vec![0i64; sink + 1];
+    let mut bounded = |network: &mut Network, from: usize, to: usize, sum: f64| {
+        // sums of fractions are off by rounding errors of floating point
+        let (lower, upper) = ((sum + 1e-9).floor(), (sum - 1e-9).ceil());
+        let (lower, upper) = (lower as i64, upper.max(lower) as i64);
+        network.add_edge(from, to, upper - lower);
+        excess[from] -= lower;
+        excess[to] += lower;
+    };
+    for (row, &sum) in row_sums.iter().enumerate() {
+        bounded(&mut network, source, row, sum);
+    }
+    for (column, &sum) in column_sums.iter().enumerate() {
+        bounded(&mut network, rows + column, sink, sum);
+    }
+    bounded(&mut network, sink, source, total);
+    let edges: Vec<Option<usize>> = values
+        .iter()
+        .zip(fractions.iter())
+        .map(|(&(row, column, _), &fraction)| {
+            if fraction > 0f64 {
+                Some(network.add_edge(row, rows + column, 1))
+            } else {
+                None
+            }
+        })
+        .collect();
+    let mut required = 0;
+    for (node, &excess) in excess.iter().enumerate() {
+        if excess > 0 {
+            network.add_edge(lower_source, node, excess);
+            required += excess;
+        } else if excess < 0 {
+            network.add_edge(node, lower_sink, -excess);
+        }
+    }
+    // the fractions themselves are a flow within the bounds, so an integral one exists
+    assert_eq!(network.max_flow(lower_source, lower_sink), required);
+
+    values
+        .iter()
+        .zip(edges)
+        .map(|(&(_, _, value), edge)| {
+            value.floor() as usize + edge.map_or(0, |edge| network.flow(edge) as usize)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn assert_within_one(values: &[(usize, usize, f64)], counts: &[usize]) {
+        let mut sums: std::collections::HashMap<(u8, usize), (f64, usize)> =
+            std::collections::HashMap::new();
+        for (&(row, column, value), &count) in values.iter().zip(counts.iter()) {
+            assert!(count as f64 == value.floor() || count as f64 == value.ceil());
+            for key in [(0, row), (1, column), (2, 0)] {
+                let sum = sums.entry(key).or_default();
+                sum.0 += value;
+                sum.1 += count;
+            }
+        }
+        for (key, (value, count)) in sums {
+            assert!((value - count as f64).abs() < 1f64, "{:?}", key);
+        }
+    }
+
+    #[test]
+    fn keeps_row_column_and_total_sums() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..20 {
+            let values: Vec<(usize, usize, f64)> = (0..12)
+                .flat_map(|row| (0..9).map(move |column| (row, column)))
+                .map(|(row, column)| (row, column, rng.gen::<f64>() * 3f64))
+                .collect();
+            assert_within_one(&values, &round(&values));
+        }
+    }
+
+    #[test]
+    fn rounds_a_small_matrix() {
+        let values = [
+            (0, 0, 0.5),
+            (0, 1, 0.5),
+            (1, 0, 0.5),
+            (1, 1, 0.5),
+            (2, 2, 2f64),
+        ];
+        let counts = round(&values);
+        assert_within_one(&values, &counts);
+        assert_eq!(counts[4], 2);
+        assert_eq!(counts.iter().sum::<usize>(), 4);
+    }
+}
@@ -1,20 +1,290 @@
+//! Sankey diagrams of the purpose flows of trips and plans.
+
+use super::dataset::Dataset;
+use super::io;
+use super::modes::Mode;
+use super::plans::Plan;
+use super::purposes::{Purpose, PURPOSES};
+use super::svg::{self, Svg};
 use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fmt::Write;
+
+const WIDTH: f64 = 960f64;
+const HEIGHT: f64 = 600f64;
+const MARGIN: f64 = 40f64;
+const LABEL_WIDTH: f64 = 170f64;
+const NODE_WIDTH: f64 = 16f64;
+const NODE_GAP: f64 = 12f64;
+const FONT_SIZE: f64 = 13f64;
+
+/// A purpose or mode column entry of a Sankey diagram.
+pub struct SankeyNode {
+    /// Label of the node.
+    pub name: String,
+    /// Position from left to right.
+    pub column: usize,
+    /// Purpose the node stands for; modes have none.
+    pub purpose: Option<Purpose>,
+}
+
+/// Weighted links between the nodes of a Sankey diagram, from origin purposes
+/// on the left, optionally via modes, to destination purposes on the right.
+pub struct Sankey {
+    /// Nodes the links refer to by index.
+    pub nodes: Vec<SankeyNode>,
+    /// Source node index, target node index and value.
+    pub links: Vec<(usize, usize, f64)>,
+}
+impl Sankey {
+    /// Purpose transitions of the input trips.
+    pub fn from_trips(dataset: &Dataset) -> Self {
+        let mut sankey = Sankey::new(&[]);
+        for trip in dataset.trips.iter() {
+            sankey.add(
+                trip.category.origin,
+                None,
+                trip.category.destination,
+                trip.count as f64,
+            );
+        }
+        sankey.finish()
+    }
+    /// Purpose transitions of the plan legs, optionally split by mode.
+    pub fn from_plans(dataset: &Dataset, plans: &[Plan], by_mode: bool) -> Self {
+        let mut sankey = Sankey::new(if by_mode { &dataset.modes } else { &[] });
+        for (_, edge) in plans.iter().flat_map(|plan| plan.legs.iter()) {
+            let mode = if by_mode { Some(edge.mode.index) } else { None };
+            sankey.add(
+                edge.trip.category.origin,
+                mode,
+                edge.trip.category.destination,
+                1f64,
+            );
+        }
+        sankey.finish()
+    }
+    /// All nodes in a fixed order: origin purposes, `modes` if split by mode,
+    /// destination purposes.
+    fn new(modes: &[Mode]) -> Self {
+        let mut nodes: Vec<SankeyNode> = PURPOSES
+            .iter()
+            .map(|&purpose| SankeyNode {
+                name: format!("from {:?}", purpose),
+                column: 0,
+                purpose: Some(purpose),
+            })
+            .collect();
+        nodes.extend(modes.iter().map(|mode| SankeyNode {
+            name: mode.name.to_string(),
+            column: 1,
+            purpose: None,
+        }));
+        let column = if modes.is_empty() { 1 } else { 2 };
+        nodes.extend(PURPOSES.iter().map(|&purpose| SankeyNode {
+            name: format!("to {:?}", purpose),
+            column,
+            purpose: Some(purpose),
+        }));
+        Sankey {
+            nodes,
+            links: Vec::new(),
+        }
+    }
+    fn add(&mut self, origin: Purpose, mode: Option<usize>, destination: Purpose, value: f64) {
+        let destination_index = self.nodes.len() - PURPOSES.len() + destination.index();
+        let mut link = |source: usize, target: usize| match self.links.iter_mut().find(
+            |(other_source, other_target, _)| (*other_source, *other_target) == (source, target),
+        ) {
+            Some(link) => link.2 += value,
+            None => self.links.push((source, target, value)),
+        };
+        match mode {
+            Some(mode_index) => {
+                link(origin.index(), PURPOSES.len() + mode_index);
+                link(PURPOSES.len() + mode_index, destination_index);
+            }
+            None => link(origin.index(), destination_index),
+        }
+    }
+    /// Removes nodes without links and orders links by source and target.
+    fn finish(mut self) -> Self {
+        let mut new_indices: HashMap<usize, usize> = HashMap::new();
+        let mut nodes: Vec<SankeyNode> = Vec::new();
+        for (index, node) in self.nodes.into_iter().enumerate() {
+            if self
+                .links
+                .iter()
+                .any(|&(source, target, _)| source == index || target == index)
+            {
+                new_indices.insert(index, nodes.len());
+                nodes.push(node);
+            }
+        }
+        for link in self.links.iter_mut() {
+            link.0 = new_indices[&link.0];
+            link.1 = new_indices[&link.1];
+        }
+        self.links
+            .sort_by_key(|&(source, target, _)| (source, target));
+        Sankey {
+            nodes,
+            links: self.links,
+        }
+    }
+    /// Input of sankeymatic.com: one "Source \[value\] Target" line per link.
+    pub fn to_sankeymatic(&self) -> String {
+        let mut text = String::new();
+        for &(source, target, value) in self.links.iter() {
+            writeln!(
+                text,
+                "{} [{}] {}",
+                self.nodes[source].name, value, self.nodes[target].name
+            )
+            .unwrap();
+        }
+        text
+    }
+    /// Input of d3-sankey: nodes by name and links by node index.
+    pub fn to_json(&self) -> String {
+        let nodes: Vec<String> = self
+            .nodes
+            .iter()
+            .map(|node| format!("{{\"name\":{}}}", json_string(&node.name)))
+            .collect();
+        let links: Vec<String> = self
+            .links
+            .iter()
+            .map(|(source, target, value)| {
+                format!(
+                    "{{\"source\":{},\"target\":{},\"value\":{}}}",
+                    source, target, value
+                )
+            })
+            .collect();
+        format!(
+            "{{\"nodes\":[{}],\"links\":[{}]}}\n",
+            nodes.join(","),
+            links.join(",")
+        )
+    }
+    /// Diagram with nodes stacked per column, heights proportional to their
+    /// values, and labels with each node's share of its column.
+    pub fn to_svg(&self, title: &str) -> Svg {
+        let columns = self.nodes.iter().map(|node| node.column).max().unwrap_or(0) + 1;
+        let values: Vec<f64> = (0..self.nodes.len())
+            .map(|index| {
+                let sum = |is_end: &dyn Fn(&(usize, usize, f64)) -> bool| -> f64 {
+                    self.links
+                        .iter()
+                        .filter(|link| is_end(link))
+                        .map(|link| link.2)
+                        .sum()
+                };
+                sum(&|link| link.0 == index).max(sum(&|link| link.1 == index))
+            })
+            .collect();
+        let column_nodes: Vec<Vec<usize>> = (0..columns)
+            .map(|column| {
+                (0..self.nodes.len())
+                    .filter(|&index| self.nodes[index].column == column)
+                    .collect()
+            })
+            .collect();
+        let column_totals: Vec<f64> = column_nodes
+            .iter()
+            .map(|indices| indices.iter().map(|&index| values[index]).sum())
+            .collect();
+        let top = 2f64 * MARGIN;
+        let inner_height = HEIGHT - top - MARGIN;
+        let scale = column_nodes
+            .iter()
+            .zip(column_totals.iter())
+            .filter(|(_, &total)| total > 0f64)
+            .map(|(indices, total)| {
+                (inner_height - NODE_GAP * (indices.len() as f64 - 1f64)) / total
+            })
+            .fold(f64::INFINITY, f64::min);
+        let left = MARGIN + LABEL_WIDTH;
+        let column_step = if columns > 1 {
+            (WIDTH - 2f64 * left - NODE_WIDTH) / (columns - 1) as f64
+        } else {
+            0f64
+        };
 
-use crate::purposes::Purpose;
-use crate::trips::TRIPS;
+        let mut positions = vec![(0f64, 0f64); self.nodes.len()];
+        for (column, indices) in column_nodes.iter().enumerate() {
+            let mut y = top;
+            for &index in indices.iter() {
+                positions[index] = (left + column as f64 * column_step, y);
+                y += values[index] * scale + NODE_GAP;
+            }
+        }
+        let colour = |index: usize| {
+            self.nodes[index]
+                .purpose
+                .map_or(svg::NEUTRAL_COLOUR, svg::purpose_colour)
+        };
 
-#[allow(dead_code)]
-pub fn main() {
-    let mut counts: HashMap<(Purpose, Purpose), usize> = HashMap::new();
-    for trip in TRIPS.iter() {
-        let key = (trip.category.origin, trip.category.destination);
-        let count = *counts.get(&key).unwrap_or(&0);
-        counts.insert(key, count + trip.count);
-    }
-    let count_sum: usize = counts.values().sum();
-    for (origin, destination) in counts.keys() {
-        let count = *counts.get(&(*origin, *destination)).unwrap();
-        let percentage = (count as f64) / (count_sum as f64) * 100f64;
-        println!("{:?} [{:.1}] {:?}'", origin, percentage, destination);
+        let mut svg = Svg::new(WIDTH, HEIGHT);
+        svg.text(WIDTH / 2f64, MARGIN, "middle", FONT_SIZE * 1.4, title);
+        let mut out_offsets = vec![0f64; self.nodes.len()];
+        let mut in_offsets = vec![0f64; self.nodes.len()];
+        for &(source, target, value) in self.links.iter() {
+            let height = value * scale;
+            let x0 = positions[source].0 + NODE_WIDTH;
+            let y0 = positions[source].1 + out_offsets[source];
+            let x1 = positions[target].0;
+            let y1 = positions[target].1 + in_offsets[target];
+            let xm = (x0 + x1) / 2f64;
+            out_offsets[source] += height;
+            in_offsets[target] += height;
+            let d = format!(
+                "M{:.2},{:.2} C{:.2},{:.2} {:.2},{:.2} {:.2},{:.2} L{:.2},{:.2} C{:.2},{:.2} {:.2},{:.2} {:.2},{:.2} Z",
+                x0, y0, xm, y0, xm, y1, x1, y1,
+                x1, y1 + height, xm, y1 + height, xm, y0 + height, x0, y0 + height
+            );
+            // links out of modes take the colour of the purpose they lead to
+            let fill = if self.nodes[source].purpose.is_some() {
+                colour(source)
+            } else {
+                colour(target)
+            };
+            let title = format!(
+                "{} \u{2192} {}: {}",
+                self.nodes[source].name, self.nodes[target].name, value
+            );
+            svg.path(&d, fill, 0.45, &title);
+        }
+        for (index, node) in self.nodes.iter().enumerate() {
+            let (x, y) = positions[index];
+            let height = values[index] * scale;
+            svg.rect(x, y, NODE_WIDTH, height, colour(index), 1f64);
+            let share = 100f64 * values[index] / column_totals[node.column];
+            let label = format!("{} {:.1}%", node.name, share);
+            if node.column == 0 {
+                svg.text(x - 6f64, y + height / 2f64, "end", FONT_SIZE, &label);
+            } else {
+                svg.text(
+                    x + NODE_WIDTH + 6f64,
+                    y + height / 2f64,
+                    "start",
+                    FONT_SIZE,
+                    &label,
+                );
+            }
+        }
+        svg
+    }
+    /// Writes `<stem>.txt` for SankeyMATIC, `<stem>.json` for d3-sankey and `<stem>.svg`.
+    pub fn write<S: AsRef<OsStr> + ?Sized>(&self, stem: &S, title: &str) {
+        let stem = stem.as_ref().to_string_lossy();
+        io::write_text(format!("{}.txt", stem), &self.to_sankeymatic());
+        io::write_text(format!("{}.json", stem), &self.to_json());
+        self.to_svg(title).write(format!("{}.svg", stem));
     }
 }
+
+fn json_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
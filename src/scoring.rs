@@ -0,0 +1,96 @@
+//! Scores ranking the valid paths of a chunk before plans are extracted.
+
+use super::capacities::Capacities;
+use super::dataset::Dataset;
+use super::filters::PotentialPath;
+use super::purposes;
+
+/// Weights of the terms of a candidate path's score. Positive weights prefer
+/// paths with a high term, negative weights avoid them.
+#[derive(Clone, Copy, Debug)]
+pub struct ScoringWeights {
+    /// Mean share of each leg's category among all trips leaving its origin purpose.
+    pub plausibility: f64,
+    /// Number of legs.
+    pub length: f64,
+    /// Mean of 1 / (1 + remaining level capacity) over the levels a path consumes.
+    pub scarcity: f64,
+}
+impl ScoringWeights {
+    /// Scores every path equally, so paths are extracted in discovery order.
+    pub const NEUTRAL: ScoringWeights = ScoringWeights {
+        plausibility: 0f64,
+        length: 0f64,
+        scarcity: 0f64,
+    };
+    fn is_neutral(&self) -> bool {
+        self.plausibility == 0f64 && self.length == 0f64 && self.scarcity == 0f64
+    }
+}
+
+/// Scores paths by the weights of a stage and the category shares of a dataset.
+pub struct Scorer {
+    weights: ScoringWeights,
+    category_shares: Vec<f64>,
+}
+impl Scorer {
+    /// Scorer with the category shares of `dataset`.
+    pub fn new(dataset: &Dataset, weights: ScoringWeights) -> Self {
+        let mut category_counts = vec![0usize; dataset.categories.len()];
+        let mut purpose_counts = [0usize; purposes::COUNT];
+        for trip in dataset.trips.iter() {
+            category_counts[trip.category.index] += trip.count;
+            purpose_counts[trip.category.origin.index()] += trip.count;
+        }
+        let category_shares = dataset
+            .categories
+            .iter()
+            .map(|category| {
+                let purpose_count = purpose_counts[category.origin.index()];
+                if purpose_count == 0 {
+                    0f64
+                } else {
+                    category_counts[category.index] as f64 / purpose_count as f64
+                }
+            })
+            .collect();
+        Scorer {
+            weights,
+            category_shares,
+        }
+    }
+    /// Weighted sum of the terms of a path under the remaining capacities.
+    pub fn score(&self, potential_path: &PotentialPath, capacities: &Capacities) -> f64 {
+        let usage = potential_path.usage();
+        let legs = potential_path.len() as f64;
+        let plausibility = usage
+            .trips
+            .iter()
+            .map(|(trip, &count)| self.category_shares[trip.category.index] * count as f64)
+            .sum::<f64>()
+            / legs;
+        let scarcity = usage
+            .levels
+            .iter()
+            .map(|((category, time_bin), &count)| {
+                count as f64 / (1 + capacities.get_level(category, *time_bin)) as f64
+            })
+            .sum::<f64>()
+            / legs;
+        self.weights.plausibility * plausibility
+            + self.weights.length * legs
+            + self.weights.scarcity * scarcity
+    }
+    /// Orders paths by descending score; equal scores keep their discovery order.
+    pub fn sort(&self, potential_paths: &mut Vec<PotentialPath>, capacities: &Capacities) {
+        if self.weights.is_neutral() {
+            return;
+        }
+        let mut scored: Vec<(f64, PotentialPath)> = potential_paths
+            .drain(..)
+            .map(|potential_path| (self.score(&potential_path, capacities), potential_path))
+            .collect();
+        scored.sort_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap());
+        potential_paths.extend(scored.into_iter().map(|(_, potential_path)| potential_path));
+    }
+}
@@ -1,44 +1,285 @@
-use super::capacities::Capacities;
+//! The staged search for plans and its configuration.
+
+use super::capacities::{Capacities, Overuse, Tolerance, Tolerances};
+use super::checkpoint::{self, Position};
+use super::constraints::{self, Context, Rejections};
+use super::dataset::Dataset;
+use super::extraction::{self, Extraction};
 use super::filters::{Filter, FilterParams, PotentialPath};
-use super::graph::{Edge, Graph, Node};
+use super::graph::{EdgeIndex, Graph, NodeIndex};
+use super::improvement::{self, Annealing};
+use super::io;
+use super::plans::Plan;
 use super::purposes::Purpose;
-use petgraph::graph::{EdgeIndex, NodeIndex};
+use super::reachability::Reachability;
+use super::residuals;
+use super::scoring::{Scorer, ScoringWeights};
+use super::stopping::{StageProgress, StopCriteria, StopReason};
+use itertools::Itertools;
 use rayon::prelude::*;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::SystemTime;
+use std::time::{Duration, Instant, SystemTime};
 
 const NUMBER_OF_CHUNKS: usize = 100;
-const FILTER_PARAMS: [FilterParams; 3] = [
-    FilterParams {
-        length_range: (3, 6),
-        first_activity: &[Purpose::Home],
-        duration_min: 40,
-        cycle: true,
+const OUTPUT_DIRECTORY: &str = "output";
+const REJECTIONS_FILE: &str = "rejections.csv";
+const OVERUSE_FILE: &str = "overuse.csv";
+const RESIDUALS_FILE: &str = "residuals.csv";
+const TOLERANCES: Tolerances = Tolerances {
+    levels: Tolerance::NONE,
+    modes: Tolerance::NONE,
+};
+/// One pass over the start nodes: which paths are valid, when to stop, and
+/// how plans are chosen among the valid paths.
+#[derive(Clone, Copy, Debug)]
+pub struct Stage {
+    /// Which paths are valid plans.
+    pub filter_params: FilterParams,
+    /// When to stop before all chunks are processed.
+    pub stop_criteria: StopCriteria,
+    /// How plans are chosen among the valid paths of a chunk.
+    pub extraction: Extraction,
+    /// Order in which the valid paths of a chunk are considered.
+    pub scoring: ScoringWeights,
+}
+const STAGES: [Stage; 3] = [
+    Stage {
+        filter_params: FilterParams {
+            length_range: (3, 6),
+            first_activity: &[Purpose::Home],
+            duration_min: 40,
+            cycle: true,
+            constraints: constraints::DEFAULT,
+        },
+        stop_criteria: StopCriteria::UNLIMITED,
+        extraction: Extraction::Greedy,
+        scoring: ScoringWeights::NEUTRAL,
     },
-    FilterParams {
-        length_range: (2, 6),
-        first_activity: &[Purpose::Home],
-        duration_min: 30,
-        cycle: true,
+    Stage {
+        filter_params: FilterParams {
+            length_range: (2, 6),
+            first_activity: &[Purpose::Home],
+            duration_min: 30,
+            cycle: true,
+            constraints: constraints::DEFAULT,
+        },
+        stop_criteria: StopCriteria::UNLIMITED,
+        extraction: Extraction::Greedy,
+        scoring: ScoringWeights::NEUTRAL,
     },
-    FilterParams {
-        length_range: (2, 6),
-        first_activity: &[Purpose::Home,Purpose::Leisure,Purpose::School,Purpose::Service,Purpose::Shopping,Purpose::Work],
-        duration_min: 1,
-        cycle: true,
+    Stage {
+        filter_params: FilterParams {
+            length_range: (2, 6),
+            first_activity: &[Purpose::Home,Purpose::Leisure,Purpose::School,Purpose::Service,Purpose::Shopping,Purpose::Work],
+            duration_min: 1,
+            cycle: true,
+            constraints: constraints::DEFAULT,
+        },
+        stop_criteria: StopCriteria::UNLIMITED,
+        extraction: Extraction::Greedy,
+        scoring: ScoringWeights::NEUTRAL,
     },
 ];
 
-pub fn search() -> Vec<Vec<(Node, Edge)>> {
+const IMPROVEMENT: Option<Annealing> = Some(Annealing {
+    iterations: 100_000,
+    temperatures: (2f64, 0.05),
+    seed: 0,
+});
+
+/// Settings of a search; the default is the one of the command line tool.
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Stages run one after another.
+    pub stages: Vec<Stage>,
+    /// How far the plans may exceed the level and mode capacities.
+    pub tolerances: Tolerances,
+    /// Improvement of the plans after the stages, if any.
+    pub improvement: Option<Annealing>,
+    /// Number of chunks the start nodes of a stage are split into; plans are
+    /// extracted and a checkpoint is written after each.
+    pub chunks: usize,
+    /// Directory of the checkpoint and of the rejection, overuse and residual statistics.
+    pub output: PathBuf,
+    /// Prints the progress of the stages and their statistics.
+    pub progress: bool,
+}
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            stages: STAGES.to_vec(),
+            tolerances: TOLERANCES,
+            improvement: IMPROVEMENT,
+            chunks: NUMBER_OF_CHUNKS,
+            output: PathBuf::from(OUTPUT_DIRECTORY),
+            progress: true,
+        }
+    }
+}
+impl Config {
+    /// Fails for settings a search cannot run with.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.stages.is_empty() {
+            return Err("A search needs at least one stage!".to_string());
+        }
+        if self.chunks == 0 {
+            return Err("The number of chunks must be positive!".to_string());
+        }
+        for (index, stage) in self.stages.iter().enumerate() {
+            let in_stage = |error: String| format!("Stage {}: {}", index + 1, error);
+            let names = stage
+                .filter_params
+                .constraints
+                .iter()
+                .map(|constraint| constraint.name);
+            if let Some(name) = names.duplicates().next() {
+                return Err(in_stage(format!(
+                    "Constraint \"{}\" is listed twice!",
+                    name
+                )));
+            }
+            stage.extraction.validate().map_err(in_stage)?;
+        }
+        Ok(())
+    }
+    /// Settings, one name and value per row.
+    pub fn describe(&self) -> Vec<(String, String)> {
+        let mut rows = vec![
+            ("Chunks per stage".to_string(), self.chunks.to_string()),
+            ("Tolerances".to_string(), format!("{:?}", self.tolerances)),
+        ];
+        for (index, stage) in self.stages.iter().enumerate() {
+            rows.push((format!("Stage {}", index + 1), format!("{:#?}", stage)));
+        }
+        rows.push((
+            "Improvement".to_string(),
+            match self.improvement {
+                Some(annealing) => format!("{:?}", annealing),
+                None => "none".to_string(),
+            },
+        ));
+        rows
+    }
+}
+
+/// What a stage or the improvement did.
+pub struct StageSummary {
+    /// Number of the stage, or "improved".
+    pub name: String,
+    /// Processed and total chunks of start nodes, both zero for a stage
+    /// without start nodes; the improvement has none.
+    pub chunks: Option<(usize, usize)>,
+    /// Edges tried by the search, or iterations of the improvement.
+    pub steps: u64,
+    /// Time spent in the stage, including that before resuming.
+    pub elapsed: Duration,
+    /// Why the stage stopped early, if it did.
+    pub stop_reason: Option<StopReason>,
+    /// Plans after the stage.
+    pub plans: usize,
+    /// Trips used by the plans after the stage.
+    pub trips_used: usize,
+}
+
+/// Result of a search.
+pub struct Run<'a> {
+    /// Settings the search ran with.
+    pub config: Config,
+    /// Plans of all stages and of the improvement.
+    pub plans: Vec<Plan<'a>>,
+    /// Stages skipped when resuming are missing.
+    pub stages: Vec<StageSummary>,
+    /// Whether the search continued after a checkpoint.
+    pub resumed: bool,
+    /// Time spent in this run.
+    pub elapsed: Duration,
+    /// Capacities of the dataset before any plan.
+    pub initial_capacities: Capacities,
+    /// Capacities remaining after all plans.
+    pub capacities: Capacities,
+}
+
+fn trips_used(plans: &[Plan]) -> usize {
+    plans.iter().map(|plan| plan.legs.len()).sum()
+}
+
+/// Runs the stages of `config` one after another on `dataset`, then the
+/// improvement. With `resume`, continues after the last checkpoint, which
+/// must have been written with the same dataset and configuration. Fails if
+/// the configuration does not validate.
+pub fn search<'a>(dataset: &'a Dataset, config: &Config, resume: bool) -> Result<Run<'a>, String> {
+    config.validate()?;
     let start = SystemTime::now();
 
-    let mut graph_arc = Arc::new(Graph::new());
-    let mut capacities_arc = Arc::new(Capacities::new());
-    let mut plans: Vec<Vec<(Node, Edge)>> = Vec::new();
+    let mut graph_arc = Arc::new(Graph::new(dataset));
+    let initial_capacities = Capacities::new(dataset, &config.tolerances);
+    let mut capacities_arc = Arc::new(initial_capacities.clone());
+    let reachability_arc = Arc::new(Reachability::new(&graph_arc));
+    if config.progress {
+        println!(
+            "Built graph: {} nodes, {:.2e} edges, reachability of {} districts and purposes",
+            graph_arc.node_count(),
+            graph_arc.edge_count(),
+            reachability_arc.key_count()
+        );
+    }
+    let mut plans: Vec<Plan<'a>> = Vec::new();
     let mut total_steps: u64 = 0;
+    let mut rejections: Vec<Rejections> = vec![Rejections::default(); config.stages.len()];
+    let trip_total = dataset.trip_total() as f64;
+    let mut summaries: Vec<StageSummary> = Vec::new();
+
+    let mut resume_position: Option<Position> = None;
+    if resume {
+        match checkpoint::read(config, dataset)? {
+            Some(checkpoint) => {
+                let position = checkpoint.position;
+                if config.progress {
+                    println!(
+                        "Resuming after stage {}, chunk {}: {} plans.",
+                        position.stage + 1,
+                        position.chunk + 1,
+                        checkpoint.plans.len()
+                    );
+                }
+                Arc::get_mut(&mut graph_arc)
+                    .unwrap()
+                    .filter_edges(&checkpoint.capacities);
+                capacities_arc = Arc::new(checkpoint.capacities);
+                plans = checkpoint.plans;
+                rejections = checkpoint.rejections;
+                rejections.resize(config.stages.len(), Rejections::default());
+                total_steps = position.total_steps;
+                resume_position = Some(position);
+            }
+            None => {
+                if config.progress {
+                    println!("No checkpoint found, starting from scratch.");
+                }
+            }
+        }
+    }
 
-    for (filter_index, filter_params) in FILTER_PARAMS.iter().enumerate() {
-        println!("\t--- STAGE {} ---", filter_index + 1);
+    for (filter_index, stage) in config.stages.iter().enumerate() {
+        let filter_params = &stage.filter_params;
+        let mut progress = StageProgress::default();
+        if let Some(position) = resume_position {
+            if filter_index < position.stage {
+                continue;
+            }
+            if filter_index == position.stage {
+                progress = position.progress;
+            }
+        }
+        if config.progress {
+            println!("\t--- STAGE {} ---", filter_index + 1);
+        }
+        let scorer = Scorer::new(dataset, stage.scoring);
+        let stage_start = Instant::now();
+        let elapsed_before = progress.elapsed;
+        let mut stop_reason = None;
+        let mut chunk_total = 0;
         let node_indices: Vec<NodeIndex> = graph_arc
             .node_indices()
             .into_iter()
@@ -50,44 +291,72 @@ pub fn search() -> Vec<Vec<(Node, Edge)>> {
                     .is_some()
             })
             .collect();
-        let chunk_size = (node_indices.len() as f64 / (NUMBER_OF_CHUNKS as f64)).ceil() as usize;
+        // a stage without start nodes has no chunks
+        let chunk_size =
+            ((node_indices.len() as f64 / (config.chunks as f64)).ceil() as usize).max(1);
 
         for (chunk_count, chunk) in node_indices.chunks(chunk_size).enumerate() {
-            let secs = start.elapsed().unwrap().as_secs();
-            println!(
-                "{:02}:{:02}:{:02} {:2.*}% {:4} plans, {:<7.2e} steps",
-                ((secs / 60) / 60) % 60,
-                (secs / 60) % 60,
-                secs % 60,
-                ((NUMBER_OF_CHUNKS / 100) as f64).log10().ceil() as usize,
-                (100 * chunk_count) as f64 / NUMBER_OF_CHUNKS as f64,
-                plans.len(),
-                total_steps,
-            );
+            if let Some(position) = resume_position {
+                if filter_index == position.stage && chunk_count <= position.chunk {
+                    chunk_total += 1;
+                    continue;
+                }
+            }
+            let trip_share = 1f64 - capacities_arc.trip_total() as f64 / trip_total;
+            stop_reason = stage.stop_criteria.check(&progress, trip_share);
+            if stop_reason.is_some() {
+                break;
+            }
+            chunk_total += 1;
+            if config.progress {
+                let secs = start.elapsed().unwrap().as_secs();
+                println!(
+                    "{:02}:{:02}:{:02} {:2.*}% {:4} plans, {:<7.2e} steps",
+                    ((secs / 60) / 60) % 60,
+                    (secs / 60) % 60,
+                    secs % 60,
+                    ((config.chunks / 100) as f64).log10().ceil() as usize,
+                    (100 * chunk_count) as f64 / config.chunks as f64,
+                    plans.len(),
+                    total_steps,
+                );
+            }
 
-            let (potential_paths, step_sum) = chunk
+            let (mut potential_paths, step_sum, chunk_rejections) = chunk
                 .par_iter()
-                .map(|&node_index| execute(graph_arc.clone(), node_index, capacities_arc.clone(), filter_params))
+                .map(|&node_index| {
+                    execute(
+                        graph_arc.clone(),
+                        node_index,
+                        capacities_arc.clone(),
+                        reachability_arc.clone(),
+                        filter_params,
+                    )
+                })
                 .reduce(
-                    || (Vec::new(), 0u64),
-                    |(mut acc, sum), (mut potential_paths, steps)| {
+                    || (Vec::new(), 0u64, Rejections::default()),
+                    |(mut acc, sum, rejections), (mut potential_paths, steps, other)| {
                         acc.append(&mut potential_paths);
-                        (acc, sum + steps)
+                        (acc, sum + steps, rejections.merge(other))
                     },
                 );
             total_steps += step_sum;
+            progress.steps += step_sum;
+            rejections[filter_index] = rejections[filter_index].clone().merge(chunk_rejections);
 
             let prev_plan_count = plans.len();
             let mut capacities = match Arc::try_unwrap(capacities_arc) {
                 Ok(capacities) => capacities,
                 Err(_) => panic!("Unwrap capacities!"),
             };
-            for potential_path in potential_paths {
-                while potential_path
-                    .try_extracting(&mut capacities, &mut plans)
-                    .is_ok()
-                {}
-            }
+            scorer.sort(&mut potential_paths, &capacities);
+            extraction::extract(
+                stage.extraction,
+                filter_index + 1,
+                &potential_paths,
+                &mut capacities,
+                &mut plans,
+            );
             capacities_arc = Arc::new(capacities);
             if plans.len() > prev_plan_count {
                 let mut graph = match Arc::try_unwrap(graph_arc) {
@@ -96,30 +365,186 @@ pub fn search() -> Vec<Vec<(Node, Edge)>> {
                 };
                 graph.filter_edges(&capacities_arc);
                 graph_arc = Arc::new(graph);
+                progress.idle_chunks = 0;
+            } else {
+                progress.idle_chunks += 1;
             }
+            progress.elapsed = elapsed_before + stage_start.elapsed();
+            let position = Position {
+                stage: filter_index,
+                chunk: chunk_count,
+                total_steps,
+                progress,
+            };
+            checkpoint::write(
+                config,
+                dataset,
+                &position,
+                &capacities_arc,
+                &plans,
+                &rejections,
+            );
         }
-        print!("Plan lengths: ");
-        for i in 1..11 {
-            let count = plans.iter().filter(|&plan| plan.len() == i).count();
-            print!("{}: {} | ", i, count);
+        let elapsed = elapsed_before + stage_start.elapsed();
+        if config.progress {
+            print!("Plan lengths: ");
+            for i in 1..11 {
+                let count = plans.iter().filter(|&plan| plan.legs.len() == i).count();
+                print!("{}: {} | ", i, count);
+            }
+            print!("total: {} |", plans.len());
+            println!("trips used: {}", trips_used(&plans));
+            let secs = elapsed.as_secs();
+            println!(
+                "{} after {} of {} chunks, {:.2e} steps, {:02}:{:02}:{:02}",
+                match stop_reason {
+                    Some(reason) => format!("Stopped ({})", reason),
+                    None => "Completed".to_string(),
+                },
+                chunk_total,
+                node_indices.chunks(chunk_size).len(),
+                progress.steps,
+                (secs / 60) / 60,
+                (secs / 60) % 60,
+                secs % 60,
+            );
+            print!("Rejections: ");
+            for constraint in filter_params.constraints.iter() {
+                let total = rejections[filter_index].total(constraint.name);
+                print!("{}: {:.2e} | ", constraint.name, total);
+            }
+            println!();
         }
-        print!("total: {} |", plans.len());
-        println!("trips used: {}", plans.iter().map(|plan| plan.len()).sum::<usize>());
+        write_rejections(&config.output, &rejections[..=filter_index]);
+        summaries.push(StageSummary {
+            name: format!("Stage {}", filter_index + 1),
+            chunks: Some((chunk_total, node_indices.chunks(chunk_size).len())),
+            steps: progress.steps,
+            elapsed,
+            stop_reason,
+            plans: plans.len(),
+            trips_used: trips_used(&plans),
+        });
     }
-    plans
+    let mut capacities = match Arc::try_unwrap(capacities_arc) {
+        Ok(capacities) => capacities,
+        Err(_) => panic!("Unwrap capacities!"),
+    };
+    if let Some(annealing) = config.improvement {
+        if config.progress {
+            println!("\t--- IMPROVEMENT ---");
+        }
+        let improvement_start = Instant::now();
+        let initial_used = trips_used(&plans);
+        let accepted = improvement::improve(
+            dataset,
+            &annealing,
+            &config
+                .stages
+                .iter()
+                .map(|stage| stage.filter_params)
+                .collect::<Vec<FilterParams>>(),
+            reachability_arc,
+            &mut capacities,
+            &mut plans,
+        );
+        if config.progress {
+            let secs = improvement_start.elapsed().as_secs();
+            println!(
+                "Improved trips used from {} to {}: {} of {} moves accepted, {} plans, {:02}:{:02}:{:02}",
+                initial_used,
+                trips_used(&plans),
+                accepted,
+                annealing.iterations,
+                plans.len(),
+                (secs / 60) / 60,
+                (secs / 60) % 60,
+                secs % 60,
+            );
+        }
+        summaries.push(StageSummary {
+            name: "Improvement".to_string(),
+            chunks: None,
+            steps: annealing.iterations,
+            elapsed: improvement_start.elapsed(),
+            stop_reason: None,
+            plans: plans.len(),
+            trips_used: trips_used(&plans),
+        });
+    }
+    write_overuse(&config.output, dataset, &capacities, config.progress);
+    let residuals = residuals::residuals(dataset, &initial_capacities, &capacities);
+    residuals::write(config.output.join(RESIDUALS_FILE), &residuals);
+    if config.progress {
+        let totals: Vec<String> = residuals::totals(&residuals)
+            .iter()
+            .map(|(name, unused, total)| format!("{}: {} of {}", name, unused, total))
+            .collect();
+        println!("Unused: {}", totals.join(" | "));
+    }
+    Ok(Run {
+        config: config.clone(),
+        plans,
+        stages: summaries,
+        resumed: resume_position.is_some(),
+        elapsed: start.elapsed().unwrap(),
+        initial_capacities,
+        capacities,
+    })
+}
+
+fn write_rejections(directory: &Path, rejections: &[Rejections]) {
+    let mut records = vec![vec![
+        "stage".to_string(),
+        "constraint".to_string(),
+        "depth".to_string(),
+        "count".to_string(),
+    ]];
+    for (stage, stage_rejections) in rejections.iter().enumerate() {
+        records.append(&mut stage_rejections.to_records(stage + 1));
+    }
+    io::write_csv(directory.join(REJECTIONS_FILE), b';', &records);
+}
+
+fn write_overuse(directory: &Path, dataset: &Dataset, capacities: &Capacities, progress: bool) {
+    let overuse = capacities.overuse(dataset);
+    let total = |capacity: &str| -> usize {
+        overuse
+            .iter()
+            .filter(|overuse| overuse.capacity == capacity)
+            .map(|overuse| overuse.count)
+            .sum()
+    };
+    if progress {
+        println!(
+            "Overuse: levels: {} | modes: {} | exceeded capacities: {}",
+            total("level"),
+            total("mode"),
+            overuse.len()
+        );
+    }
+    let mut records = vec![Overuse::HEADER.iter().map(|s| s.to_string()).collect()];
+    records.extend(overuse.iter().map(Overuse::to_record));
+    io::write_csv(directory.join(OVERUSE_FILE), b';', &records);
 }
 
-fn execute(
-    graph: Arc<Graph>,
+fn execute<'a>(
+    graph: Arc<Graph<'a>>,
     node_index: NodeIndex,
     capacities: Arc<Capacities>,
+    reachability: Arc<Reachability>,
     filter_params: &FilterParams,
-) -> (Vec<PotentialPath>, u64) {
-    let mut plans: Vec<PotentialPath> = Vec::new();
+) -> (Vec<PotentialPath<'a>>, u64, Rejections) {
+    let mut plans: Vec<PotentialPath<'a>> = Vec::new();
     let mut search_steps: u64 = 0;
-    let mut filter = match Filter::new(*filter_params, *graph.node(node_index), capacities) {
+    let context = Context {
+        params: *filter_params,
+        capacities,
+        reachability,
+    };
+    let mut filter = match Filter::new(*graph.node(node_index), &context) {
         Ok(filter) => filter,
-        Err(()) => return (plans, search_steps),
+        Err(()) => return (plans, search_steps, Rejections::default()),
     };
 
     let mut node_indices = vec![node_index];
@@ -134,37 +559,37 @@ fn execute(
         };
     }
 
-    fn to_child(
+    fn to_child<'a>(
         search_steps: &mut u64,
         edge_indices: &mut Vec<EdgeIndex>,
         node_indices: &mut Vec<NodeIndex>,
-        filter: &mut Filter,
-        graph: &Graph,
-        plans: &mut Vec<PotentialPath>,
+        filter: &mut Filter<'a>,
+        graph: &Graph<'a>,
+        plans: &mut Vec<PotentialPath<'a>>,
     ) -> bool {
         *search_steps += 1;
         let edge_index = unwrap_or_return!(graph.first_edge(*node_indices.last().unwrap()), false);
         let target_index = graph.target_index(edge_index);
         edge_indices.push(edge_index);
         node_indices.push(target_index);
-        match filter.to_child(graph.node(target_index), graph.edge(edge_index)) {
-            Err(()) => return false,
+        match filter.to_child(graph.node(target_index), &graph.edge(edge_index)) {
+            Err(()) => false,
             Ok(option) => {
-                if option.is_some() {
-                    plans.push(option.unwrap());
+                if let Some(potential_path) = option {
+                    plans.push(potential_path);
                 }
-                return true;
+                true
             }
         }
     }
 
-    fn to_sibling(
+    fn to_sibling<'a>(
         search_steps: &mut u64,
         edge_indices: &mut Vec<EdgeIndex>,
         node_indices: &mut Vec<NodeIndex>,
-        filter: &mut Filter,
-        graph: &Graph,
-        plans: &mut Vec<PotentialPath>,
+        filter: &mut Filter<'a>,
+        graph: &Graph<'a>,
+        plans: &mut Vec<PotentialPath<'a>>,
     ) -> Result<bool, ()> {
         *search_steps += 1;
         let prev_edge_index = unwrap_or_return!(edge_indices.pop(), Err(()));
@@ -184,12 +609,12 @@ fn execute(
         filter.to_parent();
         match filter.to_child(
             graph.node(sibling_target_index),
-            graph.edge(sibling_edge_index),
+            &graph.edge(sibling_edge_index),
         ) {
             Err(()) => Ok(false),
             Ok(option) => {
-                if option.is_some() {
-                    plans.push(option.unwrap());
+                if let Some(potential_path) = option {
+                    plans.push(potential_path);
                 }
                 Ok(true)
             }
@@ -234,7 +659,7 @@ fn execute(
                 Err(()) => {
                     let to_parent = to_parent(&mut edge_indices, &mut node_indices, &mut filter);
                     if !to_parent {
-                        return (plans, search_steps);
+                        return (plans, search_steps, filter.rejections);
                     }
                 }
             }
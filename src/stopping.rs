@@ -0,0 +1,75 @@
+//! When a stage stops before processing all of its chunks.
+
+use std::fmt;
+use std::time::Duration;
+
+/// Criteria for ending a stage before all chunks of start nodes are processed.
+#[derive(Clone, Copy, Debug)]
+pub struct StopCriteria {
+    /// Time spent in the stage.
+    pub time_budget: Option<Duration>,
+    /// Edges tried by the search of the stage.
+    pub max_steps: Option<u64>,
+    /// Share of all trips consumed, counted across stages.
+    pub trip_share: Option<f64>,
+    /// Number of consecutive chunks without new plans.
+    pub idle_chunks: Option<usize>,
+}
+impl StopCriteria {
+    /// Processes all chunks.
+    pub const UNLIMITED: StopCriteria = StopCriteria {
+        time_budget: None,
+        max_steps: None,
+        trip_share: None,
+        idle_chunks: None,
+    };
+    /// The first criterion met by `progress` and the share of all trips consumed, if any.
+    pub fn check(&self, progress: &StageProgress, trip_share: f64) -> Option<StopReason> {
+        if matches!(self.time_budget, Some(budget) if progress.elapsed >= budget) {
+            Some(StopReason::TimeBudget)
+        } else if matches!(self.max_steps, Some(max) if progress.steps >= max) {
+            Some(StopReason::MaxSteps)
+        } else if matches!(self.trip_share, Some(share) if trip_share >= share) {
+            Some(StopReason::TripShare)
+        } else if matches!(self.idle_chunks, Some(idle) if progress.idle_chunks >= idle) {
+            Some(StopReason::IdleChunks)
+        } else {
+            None
+        }
+    }
+}
+
+/// How far a stage got, as measured by the criteria.
+#[derive(Clone, Copy, Default)]
+pub struct StageProgress {
+    /// Time spent in the stage.
+    pub elapsed: Duration,
+    /// Edges tried by the search of the stage.
+    pub steps: u64,
+    /// Consecutive chunks without new plans.
+    pub idle_chunks: usize,
+}
+
+/// The criterion that stopped a stage.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StopReason {
+    /// See `StopCriteria::time_budget`.
+    TimeBudget,
+    /// See `StopCriteria::max_steps`.
+    MaxSteps,
+    /// See `StopCriteria::trip_share`.
+    TripShare,
+    /// See `StopCriteria::idle_chunks`.
+    IdleChunks,
+}
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Self::TimeBudget => "time budget exhausted",
+            Self::MaxSteps => "maximum steps reached",
+            Self::TripShare => "target trip share consumed",
+            Self::IdleChunks => "no new plans in recent chunks",
+        };
+        write!(f, "{}", s)
+    }
+}
@@ -0,0 +1,108 @@
+use super::io;
+use super::purposes::Purpose;
+use std::ffi::OsStr;
+use std::fmt::{self, Write};
+
+/// Colour of a purpose, the same in every diagram.
+pub fn purpose_colour(purpose: Purpose) -> &'static str {
+    match purpose {
+        Purpose::Home => "#4e79a7",
+        Purpose::Leisure => "#59a14f",
+        Purpose::Work => "#e15759",
+        Purpose::School => "#f28e2b",
+        Purpose::Service => "#b07aa1",
+        Purpose::Shopping => "#edc948",
+    }
+}
+pub const NEUTRAL_COLOUR: &str = "#9d9d9d";
+
+/// SVG document, built element by element.
+pub struct Svg {
+    width: f64,
+    height: f64,
+    elements: String,
+}
+impl Svg {
+    pub fn new(width: f64, height: f64) -> Self {
+        let mut svg = Svg {
+            width,
+            height,
+            elements: String::new(),
+        };
+        svg.rect(0f64, 0f64, width, height, "#ffffff", 1f64);
+        svg
+    }
+    pub fn rect(&mut self, x: f64, y: f64, width: f64, height: f64, fill: &str, opacity: f64) {
+        writeln!(
+            self.elements,
+            "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" fill=\"{}\" fill-opacity=\"{}\"/>",
+            x, y, width, height, fill, opacity
+        )
+        .unwrap();
+    }
+    pub fn path(&mut self, d: &str, fill: &str, opacity: f64, title: &str) {
+        writeln!(
+            self.elements,
+            "<path d=\"{}\" fill=\"{}\" fill-opacity=\"{}\"><title>{}</title></path>",
+            d,
+            fill,
+            opacity,
+            escape(title)
+        )
+        .unwrap();
+    }
+    pub fn line(
+        &mut self,
+        (x1, y1): (f64, f64),
+        (x2, y2): (f64, f64),
+        stroke: &str,
+        width: f64,
+        opacity: f64,
+        title: &str,
+    ) {
+        writeln!(
+            self.elements,
+            "<line x1=\"{:.2}\" y1=\"{:.2}\" x2=\"{:.2}\" y2=\"{:.2}\" stroke=\"{}\" stroke-width=\"{:.2}\" stroke-opacity=\"{}\" stroke-linecap=\"round\"><title>{}</title></line>",
+            x1, y1, x2, y2, stroke, width, opacity, escape(title)
+        )
+        .unwrap();
+    }
+    pub fn circle(&mut self, (cx, cy): (f64, f64), r: f64, fill: &str, opacity: f64, title: &str) {
+        writeln!(
+            self.elements,
+            "<circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"{:.2}\" fill=\"{}\" fill-opacity=\"{}\" stroke=\"#333333\" stroke-width=\"0.5\"><title>{}</title></circle>",
+            cx, cy, r, fill, opacity, escape(title)
+        )
+        .unwrap();
+    }
+    /// Text with `anchor` "start", "middle" or "end", vertically centred on `y`.
+    pub fn text(&mut self, x: f64, y: f64, anchor: &str, size: f64, text: &str) {
+        writeln!(
+            self.elements,
+            "<text x=\"{:.2}\" y=\"{:.2}\" text-anchor=\"{}\" dominant-baseline=\"middle\" font-family=\"sans-serif\" font-size=\"{}\">{}</text>",
+            x, y, anchor, size, escape(text)
+        )
+        .unwrap();
+    }
+    pub fn write<S: AsRef<OsStr>>(&self, path: S) {
+        io::write_text(path, &self.to_string());
+    }
+}
+impl fmt::Display for Svg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">",
+            self.width, self.height
+        )?;
+        write!(f, "{}", self.elements)?;
+        writeln!(f, "</svg>")
+    }
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
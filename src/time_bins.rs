@@ -1,11 +1,15 @@
+//! The half-hour time bins of a day.
+
 use lazy_static::lazy_static;
 use std::convert::TryInto;
 use std::ops::{Add, Sub};
 use std::time::Duration;
 
+/// Number of time bins of a day.
 pub const COUNT: usize = 48; // instead of u8 for external use
 const TIME_BIN_SECS: usize = 30 * 60;
 
+/// Half hour of a day, wrapping around at midnight.
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct TimeBin(u8);
 impl Add<Duration> for TimeBin {
@@ -26,12 +30,14 @@ impl Sub for TimeBin {
     }
 }
 impl TimeBin {
+    /// Index of the half hour, from 0 at midnight.
     pub fn value(&self) -> usize {
         self.0 as usize
     }
 }
 
 lazy_static! {
+    /// All time bins of a day in order.
     pub static ref TIME_BINS: [TimeBin; COUNT] = (0..COUNT)
         .map(|i| TimeBin(i as u8))
         .collect::<Vec<TimeBin>>()
@@ -1,33 +1,167 @@
-use super::categories::{Category, CATEGORIES};
+//! Trip matrices and how their demand is scaled and integerized into trips.
+
+use super::categories::Category;
 use super::districts::{self, District};
 use super::io;
-use lazy_static::lazy_static;
+use super::rounding;
+use csv::StringRecord;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::ffi::OsStr;
+use std::fmt;
 use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::str::FromStr;
 
-const TRIP_COUNT_FACTOR: f64 = 0.05;
+const LOSS_HEADER: [&str; 7] = [
+    "transport",
+    "category",
+    "demand",
+    "count",
+    "pairs",
+    "dropped_pairs",
+    "dropped_demand",
+];
 
-#[derive(Clone, Copy)]
+/// Kind of transport a trip matrix counts trips of.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Transport {
+    /// Public transport, "OV" in the input files.
     Public,
+    /// Individual transport, "IV" in the input files.
     Individual,
 }
+/// Transports in the order their matrices are loaded.
+pub const TRANSPORTS: [Transport; 2] = [Transport::Individual, Transport::Public];
 impl Transport {
-    pub fn to_str(&self) -> &'static str {
+    /// Code of the transport in the input files.
+    pub fn to_str(self) -> &'static str {
         match self {
             Self::Public => "OV",
             Self::Individual => "IV",
         }
     }
 }
+impl FromStr for Transport {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "OV" => Ok(Self::Public),
+            "IV" => Ok(Self::Individual),
+            unknown => Err(format!("Unknown transport string \"{}\"!", unknown)),
+        }
+    }
+}
+
+/// How the scaled matrix values become integer trip counts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Integerization {
+    /// Nearest integer, dropping every pair below one half.
+    Round,
+    /// The floor plus one with the probability of the fractional part.
+    Stochastic {
+        /// Seed of the random draws, so that datasets can be reloaded.
+        seed: u64,
+    },
+    /// Controlled rounding of each matrix: every pair is rounded down or up so
+    /// that the total of the matrix and of every origin and destination in it
+    /// is kept within one trip.
+    Bucket,
+}
+impl fmt::Display for Integerization {
+    /// The inverse of `from_str`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Round => write!(f, "round"),
+            Self::Bucket => write!(f, "bucket"),
+            Self::Stochastic { seed } => write!(f, "stochastic:{}", seed),
+        }
+    }
+}
+impl FromStr for Integerization {
+    type Err = String;
+    /// `round`, `bucket`, or `stochastic` with an optional seed as in `stochastic:7`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "round" => Ok(Self::Round),
+            None if s == "bucket" => Ok(Self::Bucket),
+            None if s == "stochastic" => Ok(Self::Stochastic { seed: 0 }),
+            Some(("stochastic", seed)) => seed
+                .parse()
+                .map(|seed| Self::Stochastic { seed })
+                .map_err(|_| format!("Invalid seed \"{}\"!", seed)),
+            _ => Err(format!("Unknown integerization \"{}\"!", s)),
+        }
+    }
+}
+
+/// Factor from the matrix values to trip counts, and how they are integerized.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Scaling {
+    /// Trips per unit of matrix value.
+    pub factor: f64,
+    /// How scaled values become integer counts.
+    pub integerization: Integerization,
+}
+impl Scaling {
+    /// The scaling of the command line tool without options.
+    pub const DEFAULT: Scaling = Scaling {
+        factor: 0.05,
+        integerization: Integerization::Round,
+    };
+    /// Fails with the reason if the factor cannot be used.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.factor.is_finite() && self.factor > 0f64 {
+            Ok(())
+        } else {
+            Err(format!(
+                "The trip factor must be positive and finite, not {}!",
+                self.factor
+            ))
+        }
+    }
+}
+
+/// Demand of a matrix that did not make it into the trip counts.
+#[derive(Clone, Copy, Debug)]
+pub struct Loss {
+    /// Transport of the matrix.
+    pub transport: Transport,
+    /// Category of the matrix.
+    pub category: Category,
+    /// Scaled demand of the matrix.
+    pub demand: f64,
+    /// Total count of its trips.
+    pub count: usize,
+    /// OD pairs with a positive demand.
+    pub pairs: usize,
+    /// Pairs among them without a trip.
+    pub dropped_pairs: usize,
+    /// Demand of the dropped pairs.
+    pub dropped_demand: f64,
+}
+impl Loss {
+    /// Demand minus count; negative if rounding added trips.
+    pub fn lost(&self) -> f64 {
+        self.demand - self.count as f64
+    }
+}
 
+/// Trips of one transport and category between two districts.
+#[non_exhaustive]
 pub struct Trip {
+    /// Index in the trips of the dataset.
     pub index: usize,
+    /// Transport of the matrix the trips come from.
     pub transport: Transport,
-    pub category: &'static Category,
-    pub origin: &'static District,
-    pub destination: &'static District,
+    /// Category of the matrix the trips come from.
+    pub category: Category,
+    /// Index of the origin district in the dataset.
+    pub origin_index: usize,
+    /// Index of the destination district in the dataset.
+    pub destination_index: usize,
+    /// Number of trips after scaling and integerization.
     pub count: usize,
-    _priv: (),
 }
 impl PartialEq for Trip {
     fn eq(&self, other: &Self) -> bool {
@@ -41,41 +175,134 @@ impl Hash for Trip {
     }
 }
 
-lazy_static! {
-    pub static ref TRIPS: Vec<Trip> = load();
+/// Scaled demand of the input matrix of a transport and category, with the
+/// indices of their origin and destination districts.
+pub(crate) type Demand = Vec<(usize, usize, f64)>;
+
+/// Index of the matrix of a transport and category among those of `TRANSPORTS`
+/// and `categories`, in their order.
+pub(crate) fn matrix_index(transport: Transport, category: &Category, categories: usize) -> usize {
+    let transport_index = TRANSPORTS
+        .iter()
+        .position(|&other| other == transport)
+        .unwrap();
+    transport_index * categories + category.index
 }
-fn load() -> Vec<Trip> {
+
+/// Trips of all transports and categories with a scaled count of at least
+/// one, and the demand lost per matrix by integerizing it, from the demands
+/// ordered by `matrix_index`.
+pub(crate) fn load(
+    categories: &[Category],
+    demands: &[Demand],
+    scaling: &Scaling,
+) -> (Vec<Trip>, Vec<Loss>) {
     let mut trips: Vec<Trip> = Vec::new();
-    for transport in vec![Transport::Individual, Transport::Public] {
-        for category in CATEGORIES.iter() {
-            let path = format!(
-                "verkehrsfluss/verkehrsflussdaten/{} ascii.{:03}",
-                transport.to_str(),
-                category.id.value()
-            );
-            let records = io::read_csv(path, true, false, b' ', Some(b'C'));
-            for record in records {
-                let count =
-                    (record[2].parse::<f64>().unwrap() * TRIP_COUNT_FACTOR).round() as usize;
+    let mut losses: Vec<Loss> = Vec::new();
+    let mut rng = match scaling.integerization {
+        Integerization::Stochastic { seed } => Some(StdRng::seed_from_u64(seed)),
+        _ => None,
+    };
+    for &transport in TRANSPORTS.iter() {
+        for category in categories.iter() {
+            let demands = &demands[matrix_index(transport, category, categories.len())];
+            let rounded = match scaling.integerization {
+                Integerization::Bucket => rounding::round(demands),
+                _ => Vec::new(),
+            };
+            let mut loss = Loss {
+                transport,
+                category: *category,
+                demand: 0f64,
+                count: 0,
+                pairs: 0,
+                dropped_pairs: 0,
+                dropped_demand: 0f64,
+            };
+            for (pair, &(origin_index, destination_index, demand)) in demands.iter().enumerate() {
+                let count = match scaling.integerization {
+                    Integerization::Round => demand.round() as usize,
+                    Integerization::Stochastic { .. } => {
+                        let floor = demand.floor();
+                        let up = rng.as_mut().unwrap().gen::<f64>() < demand - floor;
+                        floor as usize + up as usize
+                    }
+                    Integerization::Bucket => rounded[pair],
+                };
+                if demand > 0f64 {
+                    loss.demand += demand;
+                    loss.count += count;
+                    loss.pairs += 1;
+                }
                 if count == 0 {
+                    if demand > 0f64 {
+                        loss.dropped_pairs += 1;
+                        loss.dropped_demand += demand;
+                    }
                     continue;
                 }
                 trips.push(Trip {
                     index: trips.len(),
                     transport,
-                    category,
-                    origin: districts::parse_id(record[0].parse().unwrap()).unwrap(),
-                    destination: districts::parse_id(record[1].parse().unwrap()).unwrap(),
+                    category: *category,
+                    origin_index,
+                    destination_index,
                     count,
-                    _priv: (),
                 });
             }
+            losses.push(loss);
         }
     }
-    println!(
-        "Loaded {} distinct trips, {} total count.",
-        trips.len(),
-        trips.iter().map(|t| t.count).sum::<usize>()
-    );
-    trips
+    (trips, losses)
+}
+
+/// Writes the demand lost per matrix by integerizing it.
+pub fn write_losses<S: AsRef<OsStr>>(path: S, losses: &[Loss]) {
+    let mut records: Vec<Vec<String>> = vec![LOSS_HEADER.iter().map(|s| s.to_string()).collect()];
+    for loss in losses.iter() {
+        records.push(vec![
+            loss.transport.to_str().to_string(),
+            loss.category.id.value().to_string(),
+            format!("{:.4}", loss.demand),
+            loss.count.to_string(),
+            loss.pairs.to_string(),
+            loss.dropped_pairs.to_string(),
+            format!("{:.4}", loss.dropped_demand),
+        ]);
+    }
+    io::write_csv(path, b';', &records);
+}
+
+/// Trip counts of the input matrix of a transport and category, scaled by
+/// `factor` but not rounded.
+pub(crate) fn read_demand(
+    directory: &Path,
+    districts: &[District],
+    transport: Transport,
+    category: &Category,
+    factor: f64,
+) -> Result<Demand, String> {
+    let path = directory.join(format!(
+        "verkehrsflussdaten/{} ascii.{:03}",
+        transport.to_str(),
+        category.id.value()
+    ));
+    let in_file = |error: String| format!("{}: {}", path.display(), error);
+    let district_index = |record: &StringRecord, column: usize| -> Result<usize, String> {
+        let id: u16 = io::parse_field(record, column, "district id")?;
+        districts::parse_id(districts, id)
+            .map(|district| district.index)
+            .ok_or_else(|| format!("Unknown district {}!", id))
+    };
+    io::try_read_csv(&path, true, false, b' ', Some(b'C'))?
+        .iter()
+        .map(|record| {
+            Ok((
+                district_index(record, 0)?,
+                district_index(record, 1)?,
+                io::parse_field::<f64>(record, 2, "demand")? * factor,
+            ))
+        })
+        .collect::<Result<_, String>>()
+        .map_err(in_file)
 }
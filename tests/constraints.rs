@@ -0,0 +1,73 @@
+use day_plans::constraints::{self, Constraint, Constructor, Context, Path, Verdict};
+use day_plans::dataset::Dataset;
+use day_plans::extraction::Extraction;
+use day_plans::filters::FilterParams;
+use day_plans::purposes::Purpose;
+use day_plans::scoring::ScoringWeights;
+use day_plans::search::{self, Config, Stage};
+use day_plans::stopping::StopCriteria;
+use std::path::PathBuf;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tiny");
+
+const NO_WORK: Constructor = Constructor {
+    name: "nowork",
+    build: NoWork::build,
+};
+
+/// Prunes every path that reaches a work activity.
+struct NoWork;
+impl NoWork {
+    fn build(_context: &Context) -> Box<dyn Constraint> {
+        Box::new(NoWork)
+    }
+}
+impl Constraint for NoWork {
+    fn name(&self) -> &'static str {
+        "nowork"
+    }
+    fn check(&self, path: &Path) -> Verdict {
+        if path.nodes.last().unwrap().purpose == Purpose::Work {
+            Verdict::Prune
+        } else {
+            Verdict::Valid
+        }
+    }
+}
+
+#[test]
+fn own_constraint_filters_paths() {
+    let dataset = Dataset::load(FIXTURE).unwrap();
+    let config = Config {
+        stages: vec![Stage {
+            filter_params: FilterParams {
+                length_range: (2, 6),
+                first_activity: &[Purpose::Home],
+                duration_min: 1,
+                cycle: true,
+                constraints: &[
+                    constraints::LENGTH,
+                    constraints::DURATION,
+                    constraints::CYCLE,
+                    NO_WORK,
+                    constraints::TRIP_CAPACITY,
+                    constraints::LEVEL_CAPACITY,
+                    constraints::MODE_CAPACITY,
+                ],
+            },
+            stop_criteria: StopCriteria::UNLIMITED,
+            extraction: Extraction::Greedy,
+            scoring: ScoringWeights::NEUTRAL,
+        }],
+        improvement: None,
+        chunks: 4,
+        output: PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("own_constraint"),
+        progress: false,
+        ..Config::default()
+    };
+    let run = search::search(&dataset, &config, false).unwrap();
+    assert!(!run.plans.is_empty());
+    for (_, edge) in run.plans.iter().flat_map(|plan| plan.legs.iter()) {
+        assert_ne!(edge.trip.category.destination, Purpose::Work);
+    }
+}
@@ -0,0 +1,54 @@
+use day_plans::dataset::Dataset;
+use day_plans::graph::{Graph, Node};
+use day_plans::purposes::Purpose;
+use day_plans::reachability::Reachability;
+use day_plans::time_bins::TIME_BINS;
+
+/// See tests/search.rs: Mitte (0), Ost (1) and Nord (2).
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tiny");
+
+fn node(district_index: usize, purpose: Purpose) -> Node {
+    Node {
+        district_index,
+        purpose,
+        time_bin: TIME_BINS[10],
+    }
+}
+
+#[test]
+fn bounds_legs_and_time_bins() {
+    let dataset = Dataset::load(FIXTURE).unwrap();
+    let reachability = Reachability::new(&Graph::new(&dataset));
+
+    // a node is its own goal
+    let home = node(0, Purpose::Home);
+    assert_eq!(reachability.legs(&home, Purpose::Home), 0);
+    assert_eq!(reachability.bins(&home, Purpose::Home), 0);
+    // eight hours at home or work are 16 time bins
+    assert_eq!(reachability.legs(&home, Purpose::Work), 1);
+    assert_eq!(reachability.bins(&home, Purpose::Work), 16);
+    let work = node(1, Purpose::Work);
+    assert_eq!(reachability.legs(&work, Purpose::Home), 1);
+    assert_eq!(reachability.bins(&work, Purpose::Home), 16);
+    // shopping in Nord leads to work only through home in Mitte
+    let shopping = node(2, Purpose::Shopping);
+    assert_eq!(reachability.legs(&shopping, Purpose::Work), 2);
+    assert_eq!(reachability.bins(&shopping, Purpose::Work), 2 + 16);
+    assert_eq!(reachability.legs(&work, Purpose::Shopping), 2);
+    assert_eq!(reachability.bins(&work, Purpose::Shopping), 16 + 16);
+}
+
+#[test]
+fn marks_unreachable_purposes_and_unused_keys() {
+    let dataset = Dataset::load(FIXTURE).unwrap();
+    let reachability = Reachability::new(&Graph::new(&dataset));
+
+    // no trip leads to a school
+    let home = node(0, Purpose::Home);
+    assert_eq!(reachability.legs(&home, Purpose::School), usize::MAX);
+    assert_eq!(reachability.bins(&home, Purpose::School), usize::MAX);
+    // no trip starts or ends at home in Ost
+    let unused = node(1, Purpose::Home);
+    assert_eq!(reachability.legs(&unused, Purpose::Work), usize::MAX);
+    assert_eq!(reachability.bins(&unused, Purpose::Work), usize::MAX);
+}
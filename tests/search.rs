@@ -0,0 +1,517 @@
+use day_plans::capacities::Capacities;
+use day_plans::chains;
+use day_plans::constraints;
+use day_plans::dataset::Dataset;
+use day_plans::extraction::Extraction;
+use day_plans::filters::FilterParams;
+use day_plans::improvement::Annealing;
+use day_plans::plans::{self, Plan};
+use day_plans::purposes::Purpose;
+use day_plans::scoring::ScoringWeights;
+use day_plans::search::{self, Config, Stage};
+use day_plans::stopping::StopCriteria;
+use day_plans::time_bins::TIME_BINS;
+use day_plans::trips::Scaling;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Three districts, home-work and home-shopping categories with pegel peaks
+/// in two time bins each, and a handful of trips per matrix.
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tiny");
+
+fn output(test: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(test)
+}
+
+/// A greedy stage without a minimum duration.
+fn stage(length_range: (usize, usize), first_activity: &'static [Purpose]) -> Stage {
+    Stage {
+        filter_params: FilterParams {
+            length_range,
+            first_activity,
+            duration_min: 1,
+            cycle: true,
+            constraints: constraints::DEFAULT,
+        },
+        stop_criteria: StopCriteria::UNLIMITED,
+        extraction: Extraction::Greedy,
+        scoring: ScoringWeights::NEUTRAL,
+    }
+}
+
+fn config(test: &str, stages: Vec<Stage>) -> Config {
+    Config {
+        stages,
+        improvement: None,
+        chunks: 4,
+        output: output(test),
+        progress: false,
+        ..Config::default()
+    }
+}
+
+/// One stage over plans starting at home, without a minimum duration.
+fn single_stage(test: &str, extraction: Extraction) -> Config {
+    let stage = Stage {
+        extraction,
+        ..stage((2, 6), &[Purpose::Home])
+    };
+    config(test, vec![stage])
+}
+
+#[test]
+fn loads_fixture() {
+    let dataset = Dataset::load(FIXTURE).unwrap();
+    assert_eq!(dataset.categories.len(), 4);
+    assert_eq!(dataset.districts.len(), 3);
+    assert_eq!(dataset.levels.len(), 4);
+    // the Mitte -> Nord commute of 5 scales to 0.25 and is dropped
+    assert_eq!(dataset.trips.len(), 10);
+    assert_eq!(dataset.trip_total(), 20);
+}
+
+#[test]
+fn reports_unreadable_inputs() {
+    let error = Dataset::load(output("missing")).err().unwrap();
+    assert!(error.contains("Cannot read"), "{}", error);
+
+    let directory = output("malformed");
+    let path = directory.join("verkehrsflussdaten/categoryInformation.txt");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, "1;x;Wohnen->Arbeit\nx;x;Wohnen->Einkaufen\n").unwrap();
+    let error = Dataset::load(directory).err().unwrap();
+    assert!(error.contains("Invalid category id \"x\""), "{}", error);
+}
+
+#[test]
+fn produces_known_plans() {
+    let dataset = Dataset::load(FIXTURE).unwrap();
+    let run = search::search(
+        &dataset,
+        &single_stage("known_plans", Extraction::Greedy),
+        false,
+    )
+    .unwrap();
+    // chain pattern and departure bins per plan; modes are tied between plans
+    let mut plans: Vec<(String, Vec<usize>)> = run
+        .plans
+        .iter()
+        .map(|plan| {
+            let bins = plan.legs.iter().map(|(node, _)| node.time_bin.value());
+            (chains::pattern(plan), bins.collect())
+        })
+        .collect();
+    plans.sort();
+    let expected = vec![
+        ("H-P-H".to_string(), vec![20, 36]),
+        ("H-W-H".to_string(), vec![14, 30]),
+        ("H-W-H".to_string(), vec![14, 30]),
+    ];
+    assert_eq!(plans, expected);
+    let mut modes: Vec<&str> = run
+        .plans
+        .iter()
+        .flat_map(|plan| plan.legs.iter().map(|(_, edge)| edge.mode.name))
+        .collect();
+    modes.sort_unstable();
+    assert_eq!(
+        modes,
+        [
+            "CarDriver",
+            "CarDriver",
+            "CarDriver",
+            "Feet",
+            "Feet",
+            "Feet"
+        ]
+    );
+    assert_within(
+        &dataset,
+        &run.plans,
+        &run.initial_capacities,
+        &run.capacities,
+    );
+    // both commutes leaving home in bin 14 exhaust its level, the third plan goes shopping
+    let commute = &dataset.categories[0];
+    assert_eq!(run.initial_capacities.get_level(commute, TIME_BINS[14]), 2);
+    assert_eq!(run.capacities.get_level(commute, TIME_BINS[14]), 0);
+}
+
+/// Asserts that the plans use no trip, level or mode more often than its
+/// initial capacity allows, and that `remaining` is what they left of it.
+fn assert_within(dataset: &Dataset, plans: &[Plan], initial: &Capacities, remaining: &Capacities) {
+    let mut trips: HashMap<usize, usize> = HashMap::new();
+    let mut levels: HashMap<(usize, usize), usize> = HashMap::new();
+    let mut modes: HashMap<usize, usize> = HashMap::new();
+    for (node, edge) in plans.iter().flat_map(|plan| plan.legs.iter()) {
+        *trips.entry(edge.trip.index).or_default() += 1;
+        *levels
+            .entry((edge.trip.category.index, node.time_bin.value()))
+            .or_default() += 1;
+        *modes.entry(edge.mode.index).or_default() += 1;
+    }
+    for trip in dataset.trips.iter() {
+        let used = trips.get(&trip.index).copied().unwrap_or(0);
+        assert!(
+            used <= initial.get_trip(trip),
+            "trip {} overused",
+            trip.index
+        );
+        assert_eq!(remaining.get_trip(trip), initial.get_trip(trip) - used);
+    }
+    for category in dataset.categories.iter() {
+        for &time_bin in TIME_BINS.iter() {
+            let key = (category.index, time_bin.value());
+            let used = levels.get(&key).copied().unwrap_or(0);
+            assert!(
+                used <= initial.get_level(category, time_bin),
+                "level {:?} overused",
+                key
+            );
+            assert_eq!(
+                remaining.get_level(category, time_bin),
+                initial.get_level(category, time_bin) - used
+            );
+        }
+    }
+    for mode in dataset.modes.iter() {
+        let used = modes.get(&mode.index).copied().unwrap_or(0);
+        assert!(
+            used <= initial.get_mode(mode),
+            "mode {} overused",
+            mode.name
+        );
+        assert_eq!(remaining.get_mode(mode), initial.get_mode(mode) - used);
+    }
+}
+
+#[test]
+fn never_exceeds_capacities() {
+    let dataset = Dataset::load(FIXTURE).unwrap();
+    let config = Config {
+        chunks: 4,
+        output: output("capacities"),
+        progress: false,
+        ..Config::default()
+    };
+    let run = search::search(&dataset, &config, false).unwrap();
+    assert!(!run.plans.is_empty());
+    // the default configuration allows no overuse
+    assert_within(
+        &dataset,
+        &run.plans,
+        &run.initial_capacities,
+        &run.capacities,
+    );
+
+    // every plan continues where its previous leg arrives and survives a round trip
+    let path = config.output.join("plans.csv");
+    plans::write(&path, &dataset, &run.plans);
+    let read = plans::read(&path, &dataset).unwrap();
+    assert_eq!(
+        plans::to_records(&dataset, &read),
+        plans::to_records(&dataset, &run.plans)
+    );
+}
+
+#[test]
+fn optimal_extraction_fits_capacities() {
+    let dataset = Dataset::load(FIXTURE).unwrap();
+    let greedy = search::search(
+        &dataset,
+        &single_stage("optimal_greedy", Extraction::Greedy),
+        false,
+    )
+    .unwrap();
+    let optimal = Extraction::Optimal {
+        batch_size: 8,
+        node_limit: 1000,
+    };
+    let run = search::search(&dataset, &single_stage("optimal", optimal), false).unwrap();
+    assert_within(
+        &dataset,
+        &run.plans,
+        &run.initial_capacities,
+        &run.capacities,
+    );
+    // the solver starts from the greedy solution of each batch
+    let used = |plans: &[Plan]| plans.iter().map(|plan| plan.legs.len()).sum::<usize>();
+    assert!(used(&run.plans) >= used(&greedy.plans));
+}
+
+#[test]
+fn rejects_invalid_configs() {
+    let dataset = Dataset::load(FIXTURE).unwrap();
+    let optimal = Extraction::Optimal {
+        batch_size: 0,
+        node_limit: 1000,
+    };
+    let error = |config: &Config| search::search(&dataset, config, false).err().unwrap();
+    assert!(error(&single_stage("empty_batches", optimal)).contains("batch size"));
+    let mut config = single_stage("no_stages", Extraction::Greedy);
+    config.stages.clear();
+    assert!(error(&config).contains("at least one stage"));
+    let mut config = single_stage("repeated_constraint", Extraction::Greedy);
+    config.stages[0].filter_params.constraints = &[constraints::LENGTH, constraints::LENGTH];
+    assert!(error(&config).contains("Constraint \"length\" is listed twice"));
+}
+
+#[test]
+fn rejects_plans_without_stage_column() {
+    let dataset = Dataset::load(FIXTURE).unwrap();
+    let path = output("old_format").join("plans.csv");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(
+        &path,
+        "plan;time_bin;transport;category;origin;destination;mode\n0;14;IV;1;1;2;Feet\n",
+    )
+    .unwrap();
+    let error = plans::read(&path, &dataset).err().unwrap();
+    assert!(error.contains("plan format version 3"), "{}", error);
+}
+
+#[test]
+fn reports_unreadable_plan_files() {
+    let dataset = Dataset::load(FIXTURE).unwrap();
+    let directory = output("unreadable_plans");
+    std::fs::create_dir_all(&directory).unwrap();
+    let error = plans::read(directory.join("missing.csv"), &dataset)
+        .err()
+        .unwrap();
+    assert!(error.contains("Cannot read"), "{}", error);
+    let path = directory.join("latin1.csv");
+    std::fs::write(&path, b"plan;stage\n0;\xff\n").unwrap();
+    let error = plans::read(&path, &dataset).err().unwrap();
+    assert!(error.contains("Cannot read"), "{}", error);
+}
+
+#[test]
+fn rejects_out_of_range_ids() {
+    let dataset = Dataset::load(FIXTURE).unwrap();
+    let path = output("out_of_range").join("plans.csv");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    // 257 would become category 1 when truncated to u8
+    std::fs::write(
+        &path,
+        "scaling;0.05;round\nplan;stage;time_bin;transport;category;origin;destination;mode\n0;1;14;IV;257;1;2;Feet\n",
+    )
+    .unwrap();
+    let error = plans::read(&path, &dataset).err().unwrap();
+    assert!(error.contains("Invalid category \"257\""), "{}", error);
+}
+
+#[test]
+fn rejects_plans_of_another_scaling() {
+    let dataset = Dataset::load(FIXTURE).unwrap();
+    let config = single_stage("plans_scaling", Extraction::Greedy);
+    let run = search::search(&dataset, &config, false).unwrap();
+    let path = config.output.join("plans.csv");
+    plans::write(&path, &dataset, &run.plans);
+    let rescaled = Dataset::load_scaled(
+        FIXTURE,
+        &Scaling {
+            factor: 0.03,
+            ..Scaling::DEFAULT
+        },
+    )
+    .unwrap();
+    let error = plans::read(&path, &rescaled).err().unwrap();
+    assert!(
+        error.contains("scaled by factor 0.05 with round integerization"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn resumes_after_a_stage() {
+    let dataset = Dataset::load(FIXTURE).unwrap();
+    // the first stage stops once it used a tenth of the trips
+    let first = Stage {
+        stop_criteria: StopCriteria {
+            trip_share: Some(0.1),
+            ..StopCriteria::UNLIMITED
+        },
+        ..stage((2, 6), &[Purpose::Home])
+    };
+    let second = stage((2, 6), &[Purpose::Home]);
+    // a start node per chunk
+    let config = |test: &str, stages: Vec<Stage>| Config {
+        chunks: 1000,
+        ..config(test, stages)
+    };
+    let fresh = search::search(&dataset, &config("fresh", vec![first, second]), false).unwrap();
+    // leaves the checkpoint of where the first stage stopped, as the second
+    // one stops before its first chunk
+    let stopped = Stage {
+        stop_criteria: StopCriteria {
+            trip_share: Some(0f64),
+            ..StopCriteria::UNLIMITED
+        },
+        ..second
+    };
+    search::search(&dataset, &config("resumed", vec![first, stopped]), false).unwrap();
+    let resumed = search::search(&dataset, &config("resumed", vec![first, second]), true).unwrap();
+    assert!(resumed.resumed);
+    for stage in 1..=2 {
+        assert!(resumed.plans.iter().any(|plan| plan.stage == stage));
+    }
+    assert_eq!(
+        plans::to_records(&dataset, &resumed.plans),
+        plans::to_records(&dataset, &fresh.plans)
+    );
+    assert_within(
+        &dataset,
+        &resumed.plans,
+        &resumed.initial_capacities,
+        &resumed.capacities,
+    );
+}
+
+#[test]
+fn keeps_filter_params_per_stage() {
+    let dataset = Dataset::load(FIXTURE).unwrap();
+    // two legs from home first, then longer plans from work
+    let mut config = config(
+        "per_stage",
+        vec![
+            stage((3, 4), &[Purpose::Home]),
+            stage((2, 6), &[Purpose::Work]),
+        ],
+    );
+    config.improvement = Some(Annealing {
+        iterations: 2_000,
+        temperatures: (2f64, 0.05),
+        seed: 0,
+    });
+    let run = search::search(&dataset, &config, false).unwrap();
+    assert!(run.plans.iter().any(|plan| plan.stage == 1));
+    for plan in run.plans.iter() {
+        let first = plan.legs[0].0.purpose;
+        match plan.stage {
+            1 => assert_eq!(first, Purpose::Home),
+            2 => assert_eq!(first, Purpose::Work),
+            _ => assert!(first == Purpose::Home || first == Purpose::Work),
+        }
+        // improved plans still pass the filter of the stage they come from
+        if first == Purpose::Home {
+            assert_eq!(plan.legs.len(), 2, "{}", chains::pattern(plan));
+        }
+    }
+    assert_within(
+        &dataset,
+        &run.plans,
+        &run.initial_capacities,
+        &run.capacities,
+    );
+}
+
+#[test]
+fn refuses_to_resume_with_another_scaling() {
+    let dataset = Dataset::load(FIXTURE).unwrap();
+    let config = single_stage("scaling", Extraction::Greedy);
+    search::search(&dataset, &config, false).unwrap();
+    let rescaled = Dataset::load_scaled(
+        FIXTURE,
+        &Scaling {
+            factor: 0.03,
+            ..Scaling::DEFAULT
+        },
+    )
+    .unwrap();
+    let error = search::search(&rescaled, &config, true).err().unwrap();
+    assert!(error.contains("another trip scaling"), "{}", error);
+    assert!(search::search(&dataset, &config, true).unwrap().resumed);
+}
+
+#[test]
+fn refuses_to_resume_another_dataset_or_search() {
+    let dataset = Dataset::load(FIXTURE).unwrap();
+    let config = single_stage("fingerprint", Extraction::Greedy);
+    search::search(&dataset, &config, false).unwrap();
+
+    let mut fewer_trips = Dataset::load(FIXTURE).unwrap();
+    fewer_trips.trips.pop();
+    let error = search::search(&fewer_trips, &config, true).err().unwrap();
+    assert!(error.contains("another dataset"), "{}", error);
+    let mut more_trips = Dataset::load(FIXTURE).unwrap();
+    more_trips.trips[0].count += 1;
+    let error = search::search(&more_trips, &config, true).err().unwrap();
+    assert!(error.contains("another dataset"), "{}", error);
+
+    let more_chunks = Config {
+        chunks: 5,
+        ..config.clone()
+    };
+    let error = search::search(&dataset, &more_chunks, true).err().unwrap();
+    assert!(error.contains("another search"), "{}", error);
+    let mut more_stages = config.clone();
+    more_stages.stages.push(stage((2, 6), &[Purpose::Work]));
+    let error = search::search(&dataset, &more_stages, true).err().unwrap();
+    assert!(error.contains("another search"), "{}", error);
+}
+
+#[test]
+fn rejects_malformed_checkpoints() {
+    let dataset = Dataset::load(FIXTURE).unwrap();
+    let config = single_stage("malformed_checkpoint", Extraction::Greedy);
+    search::search(&dataset, &config, false).unwrap();
+    let path = config.output.join("checkpoint.csv");
+    let checkpoint = std::fs::read_to_string(&path).unwrap();
+
+    let garble = |from: &str, to: &str| {
+        assert!(checkpoint.contains(from));
+        std::fs::write(&path, checkpoint.replacen(from, to, 1)).unwrap();
+        search::search(&dataset, &config, true).err().unwrap()
+    };
+    let error = garble("\nposition;", "\nposition;x");
+    assert!(error.contains("Invalid position"), "{}", error);
+    let error = garble("\ntrips;", "\ntrips;x;");
+    assert!(error.contains("Invalid trips capacity"), "{}", error);
+    let error = garble("\nrejections;0;reach;", "\nrejections;0;detour;");
+    assert!(error.contains("Constraint \"detour\""), "{}", error);
+}
+
+#[test]
+fn compares_chains_with_a_reference() {
+    let directory = output("chain_reference");
+    std::fs::create_dir_all(&directory).unwrap();
+    let path = directory.join("reference.csv");
+    let error = chains::read_reference(directory.join("missing.csv"))
+        .err()
+        .unwrap();
+    assert!(error.contains("Cannot read"), "{}", error);
+    std::fs::write(&path, "pattern;value\nH-W-H;3\nH-P-H;x\n").unwrap();
+    let error = chains::read_reference(&path).err().unwrap();
+    assert!(error.contains("Invalid reference value \"x\""), "{}", error);
+    std::fs::write(&path, "pattern;value\nH-W-H;0\n").unwrap();
+    assert!(chains::read_reference(&path).is_err());
+
+    std::fs::write(&path, "pattern;value\nH-W-H;3\nH-P-H;1\n").unwrap();
+    let reference = chains::read_reference(&path).unwrap();
+    let deviation = directory.join("deviation.csv");
+    chains::compare(&reference, &deviation, &[]);
+    assert_eq!(
+        std::fs::read_to_string(&deviation).unwrap(),
+        "pattern;reference_share;realized_share;deviation\n\
+         H-P-H;0.2500;0.0000;-0.2500\n\
+         H-W-H;0.7500;0.0000;-0.7500\n"
+    );
+}
+
+#[test]
+fn skips_stages_without_start_nodes() {
+    let dataset = Dataset::load(FIXTURE).unwrap();
+    // no trip of the fixture starts at leisure
+    let config = config(
+        "no_start_nodes",
+        vec![
+            stage((2, 6), &[Purpose::Leisure]),
+            stage((2, 6), &[Purpose::Home]),
+        ],
+    );
+    let run = search::search(&dataset, &config, false).unwrap();
+    assert_eq!(run.stages[0].chunks, Some((0, 0)));
+    assert_eq!(run.stages[0].plans, 0);
+    assert!(!run.plans.is_empty());
+}
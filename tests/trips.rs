@@ -0,0 +1,128 @@
+use day_plans::dataset::Dataset;
+use day_plans::trips::{Integerization, Scaling, Transport, TRANSPORTS};
+use std::collections::HashMap;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tiny");
+
+/// Scales the fixture to fractional demands between 0.15 and 1.8.
+fn scaled(integerization: Integerization) -> Dataset {
+    Dataset::load_scaled(
+        FIXTURE,
+        &Scaling {
+            factor: 0.03,
+            integerization,
+        },
+    )
+    .unwrap()
+}
+
+/// Counts of the trips of a dataset by transport, category and OD pair.
+fn counts(dataset: &Dataset) -> HashMap<(Transport, usize, usize, usize), usize> {
+    dataset
+        .trips
+        .iter()
+        .map(|trip| {
+            let key = (
+                trip.transport,
+                trip.category.index,
+                trip.origin_index,
+                trip.destination_index,
+            );
+            (key, trip.count)
+        })
+        .collect()
+}
+
+#[test]
+fn rounding_reports_dropped_pairs() {
+    let dataset = scaled(Integerization::Round);
+    assert_eq!(dataset.trip_total(), 12);
+    let dropped: usize = dataset.losses.iter().map(|loss| loss.dropped_pairs).sum();
+    let lost: f64 = dataset.losses.iter().map(|loss| loss.lost()).sum();
+    assert_eq!(dropped, 1);
+    assert!((lost - 0.15).abs() < 1e-9);
+}
+
+#[test]
+fn bucket_rounding_keeps_totals() {
+    let dataset = scaled(Integerization::Bucket);
+    for loss in dataset.losses.iter() {
+        assert!(loss.lost().abs() < 1f64, "{:?}", loss);
+    }
+    let counts = counts(&dataset);
+    for &transport in TRANSPORTS.iter() {
+        for category in dataset.categories.iter() {
+            // demand and count by origin, by destination and of the whole matrix
+            let mut sums: HashMap<(&str, usize), (f64, usize)> = HashMap::new();
+            for &(origin, destination, demand) in dataset.demand(transport, category) {
+                let count = counts
+                    .get(&(transport, category.index, origin, destination))
+                    .copied()
+                    .unwrap_or(0);
+                assert!(count as f64 == demand.floor() || count as f64 == demand.ceil());
+                for key in [("origin", origin), ("destination", destination), ("all", 0)] {
+                    let sum = sums.entry(key).or_default();
+                    sum.0 += demand;
+                    sum.1 += count;
+                }
+            }
+            for (key, (demand, count)) in sums {
+                assert!((demand - count as f64).abs() < 1f64, "{:?}", key);
+            }
+        }
+    }
+}
+
+#[test]
+fn stochastic_rounding_is_seeded() {
+    let dataset = scaled(Integerization::Stochastic { seed: 7 });
+    let again = scaled(Integerization::Stochastic { seed: 7 });
+    assert_eq!(counts(&dataset), counts(&again));
+    let counts = counts(&dataset);
+    for &transport in TRANSPORTS.iter() {
+        for category in dataset.categories.iter() {
+            for &(origin, destination, demand) in dataset.demand(transport, category) {
+                let count = counts
+                    .get(&(transport, category.index, origin, destination))
+                    .copied()
+                    .unwrap_or(0);
+                assert!(count as f64 == demand.floor() || count as f64 == demand.ceil());
+            }
+        }
+    }
+}
+
+#[test]
+fn rejects_invalid_trip_factors() {
+    for &factor in [0f64, -0.5, f64::NAN, f64::INFINITY].iter() {
+        let scaling = Scaling {
+            factor,
+            ..Scaling::DEFAULT
+        };
+        let error = Dataset::load_scaled(FIXTURE, &scaling).err().unwrap();
+        assert!(error.contains("trip factor"), "{}", error);
+    }
+}
+
+#[test]
+fn parses_integerization() {
+    assert_eq!("round".parse(), Ok(Integerization::Round));
+    assert_eq!("bucket".parse(), Ok(Integerization::Bucket));
+    assert_eq!(
+        "stochastic".parse(),
+        Ok(Integerization::Stochastic { seed: 0 })
+    );
+    assert_eq!(
+        "stochastic:42".parse(),
+        Ok(Integerization::Stochastic { seed: 42 })
+    );
+    assert!("stochastic:x".parse::<Integerization>().is_err());
+    for integerization in [
+        Integerization::Round,
+        Integerization::Bucket,
+        Integerization::Stochastic { seed: 42 },
+    ] {
+        assert_eq!(integerization.to_string().parse(), Ok(integerization));
+    }
+    assert!("floor".parse::<Integerization>().is_err());
+}